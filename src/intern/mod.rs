@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
+// An interned name. Equal names share one handle, so comparing and hashing them is an integer
+// operation and repeated names cost no further allocation. The table is process-wide and never
+// shrinks; its strings are leaked, which is fine since a program only has so many identifiers.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+lazy_static! {
+    static ref INTERNER: Mutex<Interner> = Mutex::new(Interner::default());
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        let mut interner = INTERNER.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(symbol) = interner.symbols.get(name) {
+            return *symbol;
+        }
+        let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+        let symbol = Symbol(interner.names.len() as u32);
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+// Shows the name rather than the handle, so tokens still debug-print as `Identifier("a")`.
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intern::Symbol;
+
+    #[test]
+    fn same_handle() {
+        let a = Symbol::intern("interned");
+        let b = Symbol::intern(&String::from("interned"));
+        assert_eq!(a, b);
+        assert_ne!(a, Symbol::intern("other"));
+        assert_eq!(a.as_str(), "interned");
+        assert_eq!(format!("{} {:?}", a, a), "interned \"interned\"");
+    }
+}
@@ -1,44 +1,86 @@
-use crate::interpreter::Interpreter;
+use crate::interpreter::error::RuntimeError;
+use crate::interpreter::{is_truthy, stringify, Interpreter};
 use crate::lexer;
 use crate::parser::ast::{Literal, Stmt};
 use crate::symbol_table::{Object, SymbolTable};
-use std::cell::RefCell;
-use std::collections::HashMap;
+use crate::sync::{MaybeSend, Rc, RefCell};
+use either::Either::Right;
 use std::fmt;
 use std::fmt::{Debug, Display};
 use std::ops::Deref;
-use std::rc::Rc;
+use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-pub trait Callable: Debug + Display {
-    type Result;
-
+pub trait Callable: Debug + Display + MaybeSend {
     fn call(
         &self,
         interpreter: &mut Interpreter,
-        arguments: Vec<Self::Result>,
-    ) -> Option<Self::Result>;
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError>;
     fn arity(&self) -> usize;
+    fn as_function(&self) -> Option<&Function> {
+        None
+    }
+}
+
+pub trait TimeSource: MaybeSend {
+    fn now_secs(&self) -> f64;
+}
+
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now_secs(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs_f64()
+    }
+}
+
+pub trait RandomSource: MaybeSend {
+    // A float in [0, 1).
+    fn next_f64(&mut self) -> f64;
+}
+
+// Knuth's MMIX constants. The top 53 bits of the state fill a double's mantissa.
+pub struct Lcg {
+    state: u64,
+}
+
+impl Lcg {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self::new(nanos)
+    }
+}
+
+impl RandomSource for Lcg {
+    fn next_f64(&mut self) -> f64 {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
 }
 
 #[derive(Debug)]
 pub struct Clock;
 
 impl Callable for Clock {
-    type Result = Object;
-
     fn call(
         &self,
         interpreter: &mut Interpreter,
-        arguments: Vec<Self::Result>,
-    ) -> Option<Self::Result> {
-        let start = SystemTime::now();
-        Some(Object::L(Literal::Float(
-            start
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_secs_f64(),
-        )))
+        _arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        Ok(Object::L(Literal::Float(interpreter.clock.now_secs())))
     }
 
     fn arity(&self) -> usize {
@@ -52,53 +94,473 @@ impl fmt::Display for Clock {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
+pub struct Random;
+
+impl Callable for Random {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        Ok(Object::L(Literal::Float(interpreter.rng.next_f64())))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+impl fmt::Display for Random {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn random>")
+    }
+}
+
+// Both bounds are inclusive.
+#[derive(Debug)]
+pub struct RandInt;
+
+impl Callable for RandInt {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        match arguments.as_slice() {
+            [Object::L(Literal::Float(lo)), Object::L(Literal::Float(hi))]
+                if lo.fract() == 0.0 && hi.fract() == 0.0 && lo <= hi =>
+            {
+                let n = (interpreter.rng.next_f64() * (hi - lo + 1.0)).floor();
+                Ok(Object::L(Literal::Float(lo + n)))
+            }
+            _ => Err(RuntimeError::new(
+                "Arguments to randint must be two integers with the first no greater than the second.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+impl fmt::Display for RandInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn randint>")
+    }
+}
+
+#[derive(Debug)]
+pub struct Len;
+
+impl Callable for Len {
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        match arguments.first() {
+            Some(Object::L(Literal::String(s))) => {
+                Ok(Object::L(Literal::Float(s.chars().count() as f64)))
+            }
+            _ => Err(RuntimeError::new("Argument to len must be a string.")),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+impl fmt::Display for Len {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn len>")
+    }
+}
+
+#[derive(Debug)]
+pub struct Substr;
+
+impl Callable for Substr {
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        match arguments.as_slice() {
+            [Object::L(Literal::String(s)), Object::L(Literal::Float(start)), Object::L(Literal::Float(count))] =>
+            {
+                let len = s.chars().count() as f64;
+                if start.fract() != 0.0
+                    || count.fract() != 0.0
+                    || *start < 0.0
+                    || *count < 0.0
+                    || start + count > len
+                {
+                    return Err(RuntimeError::new("Substring index out of range."));
+                }
+                Ok(Object::L(Literal::String(
+                    s.chars()
+                        .skip(*start as usize)
+                        .take(*count as usize)
+                        .collect::<String>()
+                        .into(),
+                )))
+            }
+            _ => Err(RuntimeError::new(
+                "Arguments to substr must be a string and two numbers.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+}
+
+impl fmt::Display for Substr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn substr>")
+    }
+}
+
+#[derive(Debug)]
+pub struct Str;
+
+impl Callable for Str {
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        Ok(Object::L(Literal::String(stringify(&arguments[0]).into())))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+impl fmt::Display for Str {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn str>")
+    }
+}
+
+#[derive(Debug)]
+pub struct Num;
+
+impl Callable for Num {
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        match arguments.first() {
+            Some(Object::L(Literal::String(s))) => f64::from_str(s)
+                .map(|f| Object::L(Literal::Float(f)))
+                .map_err(|_| RuntimeError::new(&format!("Cannot convert '{}' to a number.", s))),
+            _ => Err(RuntimeError::new("Argument to num must be a string.")),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+impl fmt::Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn num>")
+    }
+}
+
+// Fractional and out-of-range numbers are rejected rather than truncated. Negative numbers keep
+// their sign in front of the prefix, so hex(-255) is "-0xff".
+fn integer_argument(arguments: &[Object], name: &str) -> Result<i64, RuntimeError> {
+    match arguments.first() {
+        Some(Object::L(Literal::Float(f))) if f.fract() == 0.0 && f.abs() < 2f64.powi(63) => {
+            Ok(*f as i64)
+        }
+        _ => Err(RuntimeError::new(&format!(
+            "Argument to {} must be an integer.",
+            name
+        ))),
+    }
+}
+
+fn sign(n: i64) -> &'static str {
+    if n < 0 {
+        "-"
+    } else {
+        ""
+    }
+}
+
+#[derive(Debug)]
+pub struct Hex;
+
+impl Callable for Hex {
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        let n = integer_argument(&arguments, "hex")?;
+        let s = format!("{}0x{:x}", sign(n), n.unsigned_abs());
+        Ok(Object::L(Literal::String(s.into())))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn hex>")
+    }
+}
+
+#[derive(Debug)]
+pub struct Bin;
+
+impl Callable for Bin {
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        let n = integer_argument(&arguments, "bin")?;
+        let s = format!("{}0b{:b}", sign(n), n.unsigned_abs());
+        Ok(Object::L(Literal::String(s.into())))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+impl fmt::Display for Bin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn bin>")
+    }
+}
+
+#[derive(Debug)]
+pub struct Input;
+
+impl Callable for Input {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        let mut line = String::new();
+        match interpreter.input.read_line(&mut line) {
+            Ok(0) => Ok(Object::L(Literal::Nil(()))),
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Ok(Object::L(Literal::String(line.into())))
+            }
+            Err(e) => Err(RuntimeError::new(&e.to_string())),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn input>")
+    }
+}
+
+#[derive(Debug)]
+pub struct TypeOf;
+
+impl Callable for TypeOf {
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        let name = match arguments[0] {
+            Object::L(Literal::Float(_)) => "number",
+            Object::L(Literal::String(_)) => "string",
+            Object::L(Literal::Bool(_)) => "bool",
+            Object::L(Literal::Nil(_)) => "nil",
+            Object::C(_) => "function",
+            Object::List(_) => "list",
+            Object::Map(_) => "map",
+        };
+        Ok(Object::L(Literal::String(name.to_string().into())))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+impl fmt::Display for TypeOf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn typeof>")
+    }
+}
+
+#[derive(Debug)]
+pub struct Assert;
+
+impl Callable for Assert {
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        if is_truthy(&arguments[0]) {
+            Ok(Object::L(Literal::Nil(())))
+        } else {
+            Err(RuntimeError::new("Assertion failed."))
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+impl fmt::Display for Assert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn assert>")
+    }
+}
+
+#[derive(Debug)]
+pub struct Globals;
+
+impl Callable for Globals {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        Ok(Object::List(Rc::new(RefCell::new(
+            interpreter
+                .globals()
+                .into_iter()
+                .map(|(name, _)| Object::L(Literal::String(name.into())))
+                .collect(),
+        ))))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+impl fmt::Display for Globals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn globals>")
+    }
+}
+
+#[cfg(not(feature = "sync"))]
+pub type NativeFn = Rc<dyn Fn(&mut Interpreter, Vec<Object>) -> Object>;
+#[cfg(feature = "sync")]
+pub type NativeFn = Rc<dyn Fn(&mut Interpreter, Vec<Object>) -> Object + Send + Sync>;
+
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl Callable for NativeFunction {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        Ok((self.function)(interpreter, arguments))
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+#[derive(Clone)]
 pub struct Function {
     pub declaration: Stmt,
-    pub closure: SymbolTable,
+    pub closure: Rc<RefCell<SymbolTable>>,
 }
 
-impl Callable for Function {
-    type Result = Object;
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("declaration", &self.declaration)
+            .finish()
+    }
+}
 
-    fn call(
+impl Function {
+    fn invoke(
         &self,
         interpreter: &mut Interpreter,
-        arguments: Vec<Self::Result>,
-    ) -> Option<Self::Result> {
+        arguments: Vec<Object>,
+    ) -> Result<(), RuntimeError> {
         if let Stmt::Function {
             ref parameters,
             ref body,
+            ref ret,
             ..
         } = self.declaration
         {
-            let mut rec_existing = SymbolTable {
-                enclosing: None,
+            let mut env = SymbolTable {
+                enclosing: Some(self.closure.clone()),
                 values: Rc::new(RefCell::new(Default::default())),
+                constants: Default::default(),
             };
-            let mut env = self.closure.deep_copy();
             if let Some(parameters) = parameters {
                 for (i, param) in parameters.iter().enumerate() {
                     if let lexer::Token::Identifier(param) = param {
-                        let arg = arguments.get(i).unwrap();
-                        if env.exists(param) {
-                            rec_existing.define(param.as_str(), arg.clone());
-                        }
-                        env.define(param.as_str(), arg.clone());
+                        env.define(*param, arguments.get(i).unwrap().clone());
                     }
                 }
             }
 
-            if let Stmt::Block(body) = body.clone().deref() {
-                let ret_env = interpreter.execute_block(body, env);
-                let names: Vec<String> = self.closure.values.borrow().keys().map(|k| String::from(k)).collect();
-                for key in names {
-                    self.closure
-                        .values
-                        .borrow_mut()
-                        .insert(key.to_string(), ret_env.get(&key));
-                }
-                interpreter.ret.take().map_or(None, |r| r.right())
+            if let Some(ret) = ret {
+                let previous_env =
+                    std::mem::replace(&mut interpreter.symbol_table, Rc::new(RefCell::new(env)));
+                let value = interpreter.evaluate(ret);
+                interpreter.symbol_table = previous_env;
+                interpreter.ret = Some(Right(value?));
+                Ok(())
+            } else if let Stmt::Block(body) = body.deref() {
+                interpreter.execute_block(body, env)
             } else {
                 panic!()
             }
@@ -106,6 +568,36 @@ impl Callable for Function {
             panic!()
         }
     }
+}
+
+impl Callable for Function {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        if interpreter.depth >= interpreter.max_depth {
+            return Err(RuntimeError::new("Stack overflow."));
+        }
+        interpreter.depth += 1;
+        let mut result = self.invoke(interpreter, arguments);
+        while result.is_ok() {
+            match interpreter.tail_call.take() {
+                Some((function, arguments)) => {
+                    interpreter.ret = None;
+                    result = function.invoke(interpreter, arguments);
+                }
+                None => break,
+            }
+        }
+        interpreter.depth -= 1;
+        result?;
+        Ok(interpreter
+            .ret
+            .take()
+            .and_then(|r| r.right())
+            .unwrap_or(Object::L(Literal::Nil(()))))
+    }
 
     fn arity(&self) -> usize {
         if let Stmt::Function { ref parameters, .. } = self.declaration {
@@ -114,6 +606,10 @@ impl Callable for Function {
             panic!()
         }
     }
+
+    fn as_function(&self) -> Option<&Function> {
+        Some(self)
+    }
 }
 
 impl fmt::Display for Function {
@@ -128,45 +624,97 @@ impl fmt::Display for Function {
 
 #[cfg(test)]
 mod tests {
-    use crate::interpreter::Interpreter;
-    use crate::lexer::lexer;
-    use crate::parser::parser::Parser;
+    use crate::interpreter::callable::{Clock, Lcg, Len, NativeFunction, RandomSource, TimeSource};
+    use crate::interpreter::error::RuntimeError;
+    use crate::interpreter::tests::{eval, expression, global, parse, run};
+    use crate::interpreter::{stringify, Interpreter};
+    use crate::parser::ast::Literal;
+    use crate::symbol_table::{Object, SymbolTable};
+    use crate::sync::{Rc, RefCell};
+    use std::io::Cursor;
 
     #[test]
     fn clock() {
-        let input: Vec<char> = r#"
+        run(r#"
             print clock();
-        "#
-        .chars()
-        .collect();
-        let tokens = lexer().parse(&input).unwrap();
-        let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        "#)
+        .unwrap();
+    }
+
+    struct FakeClock(RefCell<f64>);
+
+    impl TimeSource for FakeClock {
+        fn now_secs(&self) -> f64 {
+            let mut now = self.0.borrow_mut();
+            *now += 0.5;
+            *now - 0.5
+        }
+    }
+
+    #[test]
+    fn fake_clock() {
+        let mut i = Interpreter::new().with_clock(Box::new(FakeClock(RefCell::new(100.0))));
+        i.interpret(&parse(
+            "var start = clock(); var elapsed = clock() - start;",
+        ))
+        .unwrap();
+        let globals = i.globals.borrow();
+        assert!(matches!(
+            globals.get("start"),
+            Ok(Object::L(Literal::Float(f))) if f == 100.0
+        ));
+        assert!(matches!(
+            globals.get("elapsed"),
+            Ok(Object::L(Literal::Float(f))) if f == 0.5
+        ));
+    }
+
+    #[test]
+    fn seeded_random() {
+        let draws = |seed| {
+            let mut i = Interpreter::new().with_rng(Box::new(Lcg::new(seed)));
+            i.interpret(&parse(
+                "var a = random(); var b = random(); var c = random(); var d = randint(1, 6);",
+            ))
+            .unwrap();
+            let globals = i.globals.borrow();
+            ["a", "b", "c", "d"]
+                .iter()
+                .map(|name| match globals.get(*name) {
+                    Ok(Object::L(Literal::Float(f))) => f,
+                    other => panic!("{:?}", other),
+                })
+                .collect::<Vec<f64>>()
+        };
+
+        let mut rng = Lcg::new(42);
+        let expected: Vec<f64> = (0..3).map(|_| rng.next_f64()).collect();
+        let sequence = draws(42);
+        assert_eq!(sequence[..3], expected[..]);
+        assert_eq!(sequence, draws(42));
+        assert_ne!(sequence, draws(43));
+        assert!(sequence[..3].iter().all(|f| (0.0..1.0).contains(f)));
+        assert!(sequence[3].fract() == 0.0 && (1.0..=6.0).contains(&sequence[3]));
+
+        assert!(eval("randint(2, 1)").is_err());
     }
 
     #[test]
     fn count() {
-        let input: Vec<char> = r#"
+        run(r#"
             fun count(n) {
               if (n > 1) count(n - 1);
               print n;
             }
 
             count(3);
-        "#
-        .chars()
-        .collect();
-        let tokens = lexer().parse(&input).unwrap();
-        let mut p = Parser::new(tokens);
-        let e = p.parse();
-        let mut i = Interpreter::new();
-        i.interpret(e.as_ref());
+        "#)
+        .unwrap();
     }
 
     #[test]
     fn closure() {
-        let input: Vec<char> = r#"
+        run(r#"
             fun makeCounter() {
               var i = 0;
               fun count() {
@@ -180,19 +728,13 @@ mod tests {
             var counter = makeCounter();
             counter();
             counter();
-        "#
-        .chars()
-        .collect();
-        let tokens = lexer().parse(&input).unwrap();
-        let mut p = Parser::new(tokens);
-        let e = p.parse();
-        let mut i = Interpreter::new();
-        i.interpret(e.as_ref());
+        "#)
+        .unwrap();
     }
 
     #[test]
     fn function_global_mut() {
-        let input: Vec<char> = r#"
+        run(r#"
             var d = 4;
             fun bob() {
                 print d;
@@ -202,12 +744,184 @@ mod tests {
 
             bob();
             print d;
-        "#
-        .chars()
-        .collect();
-        let tokens = lexer().parse(&input).unwrap();
-        let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        "#)
+        .unwrap();
+    }
+
+    #[test]
+    fn len() {
+        assert!(matches!(
+            eval(r#"len("hello")"#),
+            Ok(Object::L(Literal::Float(f))) if f == 5.0
+        ));
+
+        assert_eq!(
+            eval("len(1)").unwrap_err(),
+            RuntimeError::new("Argument to len must be a string.")
+        );
+    }
+
+    #[test]
+    fn substr() {
+        assert!(matches!(
+            eval(r#"substr("hello", 1, 3)"#),
+            Ok(Object::L(Literal::String(s))) if &*s == "ell"
+        ));
+
+        assert!(matches!(
+            eval(r#"substr("héllo", 1, 1)"#),
+            Ok(Object::L(Literal::String(s))) if &*s == "é"
+        ));
+
+        assert_eq!(
+            eval(r#"substr("hello", 3, 5)"#).unwrap_err(),
+            RuntimeError::new("Substring index out of range.")
+        );
+    }
+
+    #[test]
+    fn str_num() {
+        assert!(matches!(
+            eval("str(123)"),
+            Ok(Object::L(Literal::String(s))) if &*s == "123"
+        ));
+
+        assert!(matches!(
+            eval(r#"num("2.5")"#),
+            Ok(Object::L(Literal::Float(f))) if f == 2.5
+        ));
+
+        assert!(matches!(
+            eval("num(str(42))"),
+            Ok(Object::L(Literal::Float(f))) if f == 42.0
+        ));
+
+        assert_eq!(
+            eval(r#"num("abc")"#).unwrap_err(),
+            RuntimeError::new("Cannot convert 'abc' to a number.")
+        );
+    }
+
+    #[test]
+    fn hex_bin() {
+        for (source, expected) in &[
+            ("hex(255)", "0xff"),
+            ("bin(10)", "0b1010"),
+            ("hex(0)", "0x0"),
+            ("bin(-2)", "-0b10"),
+        ] {
+            assert!(matches!(
+                eval(source),
+                Ok(Object::L(Literal::String(s))) if s.as_str() == *expected
+            ));
+        }
+
+        assert_eq!(
+            eval("hex(1.5)").unwrap_err(),
+            RuntimeError::new("Argument to hex must be an integer.")
+        );
+    }
+
+    #[test]
+    fn input() {
+        let mut i = Interpreter::new().with_input(Box::new(Cursor::new("hello\r\nworld")));
+        let e = expression("input()");
+        assert!(matches!(
+            i.evaluate(&e),
+            Ok(Object::L(Literal::String(s))) if &*s == "hello"
+        ));
+        assert!(matches!(
+            i.evaluate(&e),
+            Ok(Object::L(Literal::String(s))) if &*s == "world"
+        ));
+        assert!(matches!(i.evaluate(&e), Ok(Object::L(Literal::Nil(())))));
+    }
+
+    #[test]
+    fn type_of() {
+        for (source, expected) in &[
+            ("typeof(1)", "number"),
+            (r#"typeof("a")"#, "string"),
+            ("typeof(true)", "bool"),
+            ("typeof(nil)", "nil"),
+            ("typeof(clock)", "function"),
+            ("typeof(typeof)", "function"),
+            ("typeof([1, 2])", "list"),
+            (r#"typeof({"a": 1})"#, "map"),
+        ] {
+            assert!(matches!(
+                eval(source),
+                Ok(Object::L(Literal::String(s))) if s.as_str() == *expected
+            ));
+        }
+    }
+
+    #[test]
+    fn assert() {
+        let mut i = Interpreter::new();
+        for source in &["assert(true)", "assert(1 + 1 == 2)", r#"assert("")"#] {
+            assert!(matches!(
+                i.evaluate(&expression(source)),
+                Ok(Object::L(Literal::Nil(())))
+            ));
+        }
+        for source in &["assert(false)", "assert(nil)", "assert(1 > 2)"] {
+            assert_eq!(
+                i.evaluate(&expression(source)).unwrap_err(),
+                RuntimeError::new("Assertion failed.")
+            );
+        }
+    }
+
+    #[test]
+    fn globals() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse("var answer = 42; fun f() {} var names = globals();"))
+            .unwrap();
+        let names: Vec<String> = match global(&i, "names") {
+            Object::List(names) => names.borrow().iter().map(stringify).collect(),
+            other => panic!("{:?}", other),
+        };
+        for name in &["answer", "f", "clock", "globals"] {
+            assert!(names.contains(&name.to_string()), "{} missing", name);
+        }
+        assert!(!names.contains(&"names".to_string()));
+    }
+
+    #[test]
+    fn callables_coexist() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse("fun add(a, b) { return a + b; }"))
+            .unwrap();
+        let add = global(&i, "add");
+
+        let mut env = SymbolTable::new();
+        env.define("clock", Object::C(Rc::new(Clock {})));
+        env.define("len", Object::C(Rc::new(Len {})));
+        env.define(
+            "nil",
+            Object::C(Rc::new(NativeFunction {
+                name: "nil".to_string(),
+                arity: 2,
+                function: Rc::new(|_, _| Object::L(Literal::Nil(()))),
+            })),
+        );
+        env.define("add", add);
+
+        let mut arities = Vec::new();
+        for name in &["clock", "len", "nil", "add"] {
+            if let Ok(Object::C(c)) = env.get(*name) {
+                arities.push((c.to_string(), c.arity()));
+            }
+        }
+        assert_eq!(
+            arities,
+            vec![
+                ("<native fn clock>".to_string(), 0),
+                ("<native fn len>".to_string(), 1),
+                ("<native fn nil>".to_string(), 2),
+                ("<fn add>".to_string(), 2),
+            ]
+        );
     }
 }
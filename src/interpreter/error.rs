@@ -0,0 +1,47 @@
+use crate::lexer::Position;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl RuntimeError {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: message.to_owned(),
+            line: None,
+            column: None,
+        }
+    }
+
+    // Errors bubble out through every enclosing expression, so the innermost line wins.
+    pub fn at(mut self, line: Option<usize>) -> Self {
+        if self.line.is_none() {
+            self.line = line;
+        }
+        self
+    }
+
+    pub fn at_position(mut self, position: Option<Position>) -> Self {
+        if let (None, Some((line, column))) = (self.line, position) {
+            self.line = Some(line);
+            self.column = Some(column);
+        }
+        self
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "[line {}] {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for RuntimeError {}
@@ -1,81 +1,434 @@
-use crate::interpreter::callable::{Clock, Function};
+use crate::intern::Symbol;
+use crate::interpreter::callable::{
+    Assert, Bin, Callable, Clock, Globals, Hex, Input, Lcg, Len, NativeFn, NativeFunction, Num,
+    RandInt, Random, RandomSource, Str, Substr, SystemClock, TimeSource, TypeOf,
+};
+use crate::interpreter::error::RuntimeError;
 use crate::lexer;
 use crate::lexer::Operator;
 use crate::parser::ast::{Expr, ExprData, ExprVisitor, Literal, Stmt, StmtData, StmtVisitor};
+use crate::parser::parser::Parser;
 use crate::symbol_table::{Object, SymbolTable};
+use crate::sync::{Rc, RefCell};
 use either::Either;
 use either::Either::{Left, Right};
-use std::cell::RefCell;
-use std::ops::Deref;
-use std::option::Option::Some;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 
 pub mod callable;
+pub mod error;
+pub mod session;
+
+#[cfg(not(feature = "sync"))]
+pub type InputStream = Box<dyn BufRead>;
+#[cfg(not(feature = "sync"))]
+pub type OutputStream = Box<dyn Write>;
+#[cfg(feature = "sync")]
+pub type InputStream = Box<dyn BufRead + Send>;
+#[cfg(feature = "sync")]
+pub type OutputStream = Box<dyn Write + Send>;
 
 pub struct Interpreter {
     symbol_table: Rc<RefCell<SymbolTable>>,
     globals: Rc<RefCell<SymbolTable>>,
     ret: Option<Either<(), Object>>,
+    last_value: Option<Object>,
+    tail_call: Option<(callable::Function, Vec<Object>)>,
+    depth: usize,
+    max_depth: usize,
+    loop_limit: Option<usize>,
+    input: InputStream,
+    output: OutputStream,
+    clock: Box<dyn TimeSource>,
+    rng: Box<dyn RandomSource>,
+    base_dir: PathBuf,
+    imported: HashSet<PathBuf>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         let mut globals = SymbolTable::new();
         globals.define("clock", Object::C(Rc::new(Clock {})));
+        globals.define("random", Object::C(Rc::new(Random {})));
+        globals.define("randint", Object::C(Rc::new(RandInt {})));
+        globals.define("len", Object::C(Rc::new(Len {})));
+        globals.define("substr", Object::C(Rc::new(Substr {})));
+        globals.define("str", Object::C(Rc::new(Str {})));
+        globals.define("num", Object::C(Rc::new(Num {})));
+        globals.define("hex", Object::C(Rc::new(Hex {})));
+        globals.define("bin", Object::C(Rc::new(Bin {})));
+        globals.define("input", Object::C(Rc::new(Input {})));
+        globals.define("typeof", Object::C(Rc::new(TypeOf {})));
+        globals.define("assert", Object::C(Rc::new(Assert {})));
+        globals.define("globals", Object::C(Rc::new(Globals {})));
         let globals = Rc::new(RefCell::new(globals));
         Self {
             symbol_table: globals.clone(),
             globals,
             ret: None,
+            last_value: None,
+            tail_call: None,
+            depth: 0,
+            max_depth: 1000,
+            loop_limit: None,
+            input: Box::new(BufReader::new(io::stdin())),
+            output: Box::new(io::stdout()),
+            clock: Box::new(SystemClock),
+            rng: Box::new(Lcg::from_time()),
+            base_dir: PathBuf::new(),
+            imported: HashSet::new(),
         }
     }
 
-    pub fn evaluate(&mut self, expr: &Expr) -> Option<Object> {
+    pub fn register_native(&mut self, name: &str, arity: usize, f: NativeFn) {
+        self.globals.borrow_mut().define(
+            name,
+            Object::C(Rc::new(NativeFunction {
+                name: name.to_string(),
+                arity,
+                function: f,
+            })),
+        );
+    }
+
+    pub fn with_input(mut self, input: InputStream) -> Self {
+        self.input = input;
+        self
+    }
+
+    pub fn with_output(mut self, output: OutputStream) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Box<dyn TimeSource>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_rng(mut self, rng: Box<dyn RandomSource>) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_loop_limit(mut self, loop_limit: usize) -> Self {
+        self.loop_limit = Some(loop_limit);
+        self
+    }
+
+    pub fn with_script(mut self, path: &Path) -> Self {
+        if let Some(parent) = path.parent() {
+            self.base_dir = parent.to_path_buf();
+        }
+        if let Ok(path) = path.canonicalize() {
+            self.imported.insert(path);
+        }
+        self
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Object, RuntimeError> {
         expr.accept(self)
+            .map_err(|e| e.at_position(expr.position()))
     }
 
-    pub fn interpret(&mut self, stmts: &[Stmt]) {
+    // `s = s + x` and `s += x` let go of the variable's string before adding to it, so that it
+    // usually has no other owner and is appended to in place rather than copied.
+    fn append_assign(
+        &mut self,
+        name: Symbol,
+        left: &Expr,
+        right: &Expr,
+        position: Option<lexer::Position>,
+    ) -> Result<Object, RuntimeError> {
+        let left = self.evaluate(left)?;
+        let right = self.evaluate(right)?;
+        if let (Object::L(Literal::String(_)), Object::L(Literal::String(_))) = (&left, &right) {
+            self.symbol_table
+                .borrow_mut()
+                .assign(name, Object::L(Literal::Nil(())))?;
+        }
+        let value = binary(&Operator::Plus, left, right).map_err(|e| e.at_position(position))?;
+        self.symbol_table.borrow_mut().assign(name, value.clone())?;
+        Ok(value)
+    }
+
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<(), RuntimeError> {
+        self.last_value = None;
         for stmt in stmts {
-            self.execute(stmt)
+            self.execute(stmt)?
         }
+        Ok(())
+    }
+
+    // Drops whatever call state a panic unwinding out of `interpret` left behind, keeping globals.
+    pub fn recover(&mut self) {
+        self.symbol_table = self.globals.clone();
+        self.ret = None;
+        self.last_value = None;
+        self.tail_call = None;
+        self.depth = 0;
     }
 
-    pub fn execute(&mut self, stmt: &Stmt) {
+    // The value of the most recent expression statement run by the last `interpret`, for the REPL
+    // to echo.
+    pub fn take_last_value(&mut self) -> Option<Object> {
+        self.last_value.take()
+    }
+
+    pub fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
         if self.ret.is_none() {
-            stmt.accept(self)
+            stmt.accept(self)?
         }
+        Ok(())
     }
 
-    pub fn execute_block(&mut self, stmts: &[Stmt], environment: SymbolTable) -> SymbolTable {
+    pub fn globals(&self) -> Vec<(String, Object)> {
+        let mut globals: Vec<(String, Object)> = self
+            .globals
+            .borrow()
+            .values
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        globals
+    }
+
+    pub fn dump_globals(&self) -> String {
+        let mut globals: Vec<(String, Literal)> = self
+            .globals
+            .borrow()
+            .values
+            .borrow()
+            .iter()
+            .filter_map(|(name, value)| match value {
+                Object::L(l) => Some((name.to_string(), l.clone())),
+                _ => None,
+            })
+            .collect();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        session::to_json(&globals)
+    }
+
+    pub fn load_globals(&mut self, json: &str) -> Result<(), RuntimeError> {
+        for (name, value) in session::from_json(json)? {
+            self.globals.borrow_mut().define(&name, Object::L(value));
+        }
+        Ok(())
+    }
+
+    fn callee_and_args(
+        &mut self,
+        callee: &Expr,
+        arguments: &[Expr],
+    ) -> Result<(Rc<dyn Callable>, Vec<Object>), RuntimeError> {
+        let callee = match self.evaluate(callee)? {
+            Object::C(callee) => callee,
+            _ => return Err(RuntimeError::new("Can only call functions and classes.")),
+        };
+        let mut args = Vec::new();
+        for argument in arguments {
+            args.push(self.evaluate(argument)?)
+        }
+        if args.len() != callee.arity() {
+            return Err(RuntimeError::new(&format!(
+                "Expected {} arguments but got {}.",
+                callee.arity(),
+                args.len()
+            )));
+        }
+        Ok((callee, args))
+    }
+
+    pub fn execute_block(
+        &mut self,
+        stmts: &[Stmt],
+        environment: SymbolTable,
+    ) -> Result<(), RuntimeError> {
         let previous_env =
             std::mem::replace(&mut self.symbol_table, Rc::new(RefCell::new(environment)));
-        for stmt in stmts {
-            self.execute(stmt)
+        let result = stmts.iter().try_for_each(|stmt| self.execute(stmt));
+        self.symbol_table = previous_env;
+        result
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+// The longest string `string * count` may build, in bytes.
+pub const MAX_REPEAT_LENGTH: usize = 1 << 30;
+
+pub fn is_truthy(object: &Object) -> bool {
+    match object {
+        Object::L(Literal::Nil(_)) => false,
+        Object::L(Literal::Bool(b)) => *b,
+        _ => true,
+    }
+}
+
+pub fn stringify(object: &Object) -> String {
+    object.to_string()
+}
+
+fn map_key(key: &Object) -> Result<Object, RuntimeError> {
+    match key {
+        Object::L(Literal::Float(f)) if f.is_nan() => {
+            Err(RuntimeError::new("NaN can't be used as a map key."))
+        }
+        key => Ok(key.clone()),
+    }
+}
+
+fn list_index(list: &[Object], index: &Object) -> Result<usize, RuntimeError> {
+    match index {
+        Object::L(Literal::Float(i)) if i.fract() == 0.0 => {
+            if *i >= 0.0 && (*i as usize) < list.len() {
+                Ok(*i as usize)
+            } else {
+                Err(RuntimeError::new("Index out of bounds."))
+            }
+        }
+        _ => Err(RuntimeError::new("List index must be an integer.")),
+    }
+}
+
+fn as_integer(object: &Object) -> Result<i64, RuntimeError> {
+    match object {
+        Object::L(Literal::Float(f)) if f.fract() == 0.0 => Ok(*f as i64),
+        _ => Err(RuntimeError::new("Operands must be integers.")),
+    }
+}
+
+fn bitwise(operator: &Operator, left: &Object, right: &Object) -> Result<Object, RuntimeError> {
+    let left = as_integer(left)?;
+    let right = as_integer(right)?;
+    let shift = |right: i64| {
+        if (0..64).contains(&right) {
+            Ok(right as u32)
+        } else {
+            Err(RuntimeError::new("Shift amount out of range."))
+        }
+    };
+    let result = match operator {
+        Operator::BitAnd => left & right,
+        Operator::BitOr => left | right,
+        Operator::BitXor => left ^ right,
+        Operator::ShiftLeft => left << shift(right)?,
+        Operator::ShiftRight => left >> shift(right)?,
+        _ => panic!("{:?}", operator),
+    };
+    Ok(Object::L(Literal::Float(result as f64)))
+}
+
+fn binary(operator: &Operator, left: Object, right: Object) -> Result<Object, RuntimeError> {
+    if let Operator::BitAnd
+    | Operator::BitOr
+    | Operator::BitXor
+    | Operator::ShiftLeft
+    | Operator::ShiftRight = operator
+    {
+        return bitwise(operator, &left, &right);
+    }
+    if let Operator::Equal | Operator::NotEqual = operator {
+        let equal = left == right;
+        return Ok(Object::L(Literal::Bool(
+            equal == (*operator == Operator::Equal),
+        )));
+    }
+    match (left, right) {
+        (Object::L(Literal::String(left)), Object::L(Literal::String(right))) => {
+            return Ok(Object::L(match operator {
+                Operator::Plus => Literal::append(left, &right),
+                Operator::GreaterThanOrEqual => Literal::Bool(left >= right),
+                Operator::LessThanOrEqual => Literal::Bool(left <= right),
+                Operator::GreaterThan => Literal::Bool(left > right),
+                Operator::LessThan => Literal::Bool(left < right),
+                _ => return Err(RuntimeError::new("Operands must be numbers.")),
+            }));
+        }
+        (Object::L(Literal::String(left)), Object::L(Literal::Float(count)))
+            if *operator == Operator::Star =>
+        {
+            if count < 0.0 || count.fract() != 0.0 {
+                return Err(RuntimeError::new(
+                    "String repetition count must be a non-negative integer.",
+                ));
+            }
+            // The count is bounded before the cast so that a huge float can't saturate.
+            let length = Some(count)
+                .filter(|count| *count <= MAX_REPEAT_LENGTH as f64)
+                .and_then(|count| left.len().checked_mul(count as usize))
+                .filter(|length| *length <= MAX_REPEAT_LENGTH);
+            if length.is_none() {
+                return Err(RuntimeError::new("String repetition result is too long."));
+            }
+            return Ok(Object::L(Literal::String(
+                left.repeat(count as usize).into(),
+            )));
+        }
+        (Object::L(Literal::Float(left)), Object::L(Literal::Float(right))) => {
+            if *operator == Operator::Slash && right == 0.0 {
+                return Err(RuntimeError::new("Division by zero."));
+            }
+            return Ok(Object::L(match operator {
+                Operator::Minus => Literal::Float(left - right),
+                Operator::Plus => Literal::Float(left + right),
+                Operator::Slash => Literal::Float(left / right),
+                Operator::Star => Literal::Float(left * right),
+                Operator::Power => Literal::Float(left.powf(right)),
+                Operator::GreaterThanOrEqual => Literal::Bool(left >= right),
+                Operator::LessThanOrEqual => Literal::Bool(left <= right),
+                Operator::GreaterThan => Literal::Bool(left > right),
+                Operator::LessThan => Literal::Bool(left < right),
+                Operator::And => Literal::Bool(left > 0.0 && right > 0.0),
+                Operator::Or => Literal::Bool(left > 0.0 || right > 0.0),
+                _ => panic!("{:?}", operator),
+            }));
         }
-        std::mem::replace(&mut self.symbol_table, previous_env).borrow_mut().deep_copy()
+        _ => {}
     }
+    // Only numbers, and strings where they're handled above, get this far.
+    Err(RuntimeError::new(match operator {
+        Operator::Plus => "Operands must be two numbers or two strings.",
+        _ => "Operands must be numbers.",
+    }))
 }
 
 impl ExprVisitor for Interpreter {
-    type Result = Object;
+    type Result = Result<Object, RuntimeError>;
 
-    fn visit_literal(&mut self, expr: &Literal) -> Option<Self::Result> {
-        Some(Object::L(expr.clone()))
+    fn visit_literal(&mut self, expr: &Literal) -> Self::Result {
+        Ok(Object::L(expr.clone()))
     }
 
-    fn visit_unary(&mut self, expr: &Expr) -> Option<Self::Result> {
-        if let Expr::Unary { operator, right } = expr {
-            let right = self.evaluate(right);
+    fn visit_unary(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Unary {
+            operator, right, ..
+        } = expr
+        {
+            let right = self.evaluate(right)?;
             if *operator == Operator::Minus {
-                return right.map(|r| match r {
-                    Object::L(Literal::Float(l)) => Object::L(Literal::Float(-l)),
-                    _ => panic!("{:?}", r),
-                });
+                return match right {
+                    Object::L(Literal::Float(l)) => Ok(Object::L(Literal::Float(-l))),
+                    _ => Err(RuntimeError::new("Operand must be a number.")),
+                };
             } else if *operator == Operator::Not {
-                return right.map(|r| match r {
-                    Object::L(Literal::Bool(b)) => Object::L(Literal::Bool(!b)),
-                    _ => panic!("{:?}", r),
-                });
+                return Ok(Object::L(Literal::Bool(!is_truthy(&right))));
             } else {
                 panic!("{:?}", operator)
             };
@@ -83,215 +436,424 @@ impl ExprVisitor for Interpreter {
         panic!("{:?}", expr)
     }
 
-    fn visit_binary(&mut self, expr: &Expr) -> Option<Self::Result> {
+    fn visit_binary(&mut self, expr: &Expr) -> Self::Result {
         if let Expr::Binary {
             left,
             operator,
             right,
+            ..
         } = expr
         {
-            if let Some(Object::L(Literal::String(left))) = self.evaluate(left) {
-                if let Some(Object::L(Literal::String(right))) = self.evaluate(right) {
-                    return Some(Object::L(match operator {
-                        Operator::Plus => Literal::String(left + right.as_str()),
-                        _ => panic!("{:?}", operator),
-                    }));
-                }
-            }
-            if let Some(Object::L(Literal::Float(left))) = self.evaluate(left) {
-                if let Some(Object::L(Literal::Float(right))) = self.evaluate(right) {
-                    return Some(Object::L(match operator {
-                        Operator::Minus => Literal::Float(left - right),
-                        Operator::Plus => Literal::Float(left + right),
-                        Operator::Slash => Literal::Float(left / right),
-                        Operator::Star => Literal::Float(left * right),
-                        Operator::NotEqual => Literal::Bool(left != right),
-                        Operator::Equal => Literal::Bool(left == right),
-                        Operator::GreaterThanOrEqual => Literal::Bool(left >= right),
-                        Operator::LessThanOrEqual => Literal::Bool(left <= right),
-                        Operator::GreaterThan => Literal::Bool(left > right),
-                        Operator::LessThan => Literal::Bool(left < right),
-                        Operator::And => Literal::Bool(left > 0.0 && right > 0.0),
-                        Operator::Or => Literal::Bool(left > 0.0 || right > 0.0),
-                        _ => panic!("{:?}", operator),
-                    }));
-                }
-            }
+            let left = self.evaluate(left)?;
+            let right = self.evaluate(right)?;
+            return binary(operator, left, right);
         }
         panic!("{:?}", expr)
     }
 
-    fn visit_logical(&mut self, expr: &Expr) -> Option<Self::Result> {
+    fn visit_logical(&mut self, expr: &Expr) -> Self::Result {
         if let Expr::Logical {
             left,
             operator,
             right,
         } = expr
         {
-            if let Some(Object::L(Literal::Bool(left))) = self.evaluate(left) {
-                if let Some(Object::L(Literal::Bool(right))) = self.evaluate(right) {
-                    return Some(Object::L(match operator {
-                        Operator::And => Literal::Bool(left && right),
-                        Operator::Or => Literal::Bool(left || right),
-                        _ => panic!("{:?}", operator),
-                    }));
+            let left = self.evaluate(left)?;
+            return match operator {
+                Operator::Or if is_truthy(&left) => Ok(left),
+                Operator::And if !is_truthy(&left) => Ok(left),
+                Operator::Coalesce if !matches!(left, Object::L(Literal::Nil(_))) => Ok(left),
+                Operator::And | Operator::Or | Operator::Coalesce => self.evaluate(right),
+                _ => panic!("{:?}", operator),
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_grouping(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Grouping { expression } = expr {
+            return self.evaluate(expression);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_assign(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Assign {
+            name: lexer::Token::Identifier(name),
+            value,
+        } = expr
+        {
+            if let Expr::Binary {
+                left,
+                operator: Operator::Plus,
+                right,
+                position,
+            } = &**value
+            {
+                if matches!(&**left, Expr::Variable { name: lexer::Token::Identifier(target), .. } if target == name)
+                {
+                    return self.append_assign(*name, left, right, *position);
                 }
             }
+            let value = self.evaluate(value)?;
+            self.symbol_table
+                .borrow_mut()
+                .assign(*name, value.clone())?;
+            return Ok(value);
         }
         panic!("{:?}", expr)
     }
 
-    fn visit_grouping(&mut self, expr: &Expr) -> Option<Self::Result> {
-        unimplemented!()
+    fn visit_postfix(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Postfix {
+            name: lexer::Token::Identifier(name),
+            operator,
+            ..
+        } = expr
+        {
+            let old = match self.symbol_table.borrow().get(*name)? {
+                Object::L(Literal::Float(old)) => old,
+                _ => return Err(RuntimeError::new("Operand must be a number.")),
+            };
+            let new = match operator {
+                Operator::Plus => old + 1.0,
+                _ => old - 1.0,
+            };
+            self.symbol_table
+                .borrow_mut()
+                .assign(*name, Object::L(Literal::Float(new)))?;
+            return Ok(Object::L(Literal::Float(old)));
+        }
+        panic!("{:?}", expr)
     }
 
-    fn visit_assign(&mut self, expr: &Expr) -> Option<Self::Result> {
-        if let Expr::Assign { name, value } = expr {
-            if let lexer::Token::Identifier(name) = name {
-                let value = self.evaluate(value);
-                self.symbol_table
-                    .borrow_mut()
-                    .assign(name, value.clone().unwrap());
-                return value;
+    fn visit_variable(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Variable {
+            name: lexer::Token::Identifier(name),
+            ..
+        } = expr
+        {
+            return self.symbol_table.borrow().get(*name);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_call(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Call {
+            callee, arguments, ..
+        } = expr
+        {
+            let (callee, args) = self.callee_and_args(callee, arguments)?;
+            return callee.call(self, args);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_list(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::List { elements } = expr {
+            let mut list = Vec::new();
+            for element in elements {
+                list.push(self.evaluate(element)?);
             }
+            return Ok(Object::List(Rc::new(RefCell::new(list))));
         }
         panic!("{:?}", expr)
     }
 
-    fn visit_variable(&mut self, expr: &Expr) -> Option<Self::Result> {
-        if let Expr::Variable { name } = expr {
-            if let lexer::Token::Identifier(name) = name {
-                return Some(self.symbol_table.borrow_mut().get(name));
+    fn visit_map(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Map { entries } = expr {
+            let mut map = HashMap::new();
+            for (key, value) in entries {
+                let key = map_key(&self.evaluate(key)?)?;
+                map.insert(key, self.evaluate(value)?);
             }
+            return Ok(Object::Map(Rc::new(RefCell::new(map))));
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_index(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Index { object, index } = expr {
+            let object = self.evaluate(object)?;
+            let index = self.evaluate(index)?;
+            return match object {
+                Object::List(list) => {
+                    let list = list.borrow();
+                    Ok(list[list_index(&list, &index)?].clone())
+                }
+                Object::Map(map) => Ok(map
+                    .borrow()
+                    .get(&map_key(&index)?)
+                    .cloned()
+                    .unwrap_or(Object::L(Literal::Nil(())))),
+                _ => Err(RuntimeError::new("Only lists and maps can be indexed.")),
+            };
         }
         panic!("{:?}", expr)
     }
 
-    fn visit_call(&mut self, expr: &Expr) -> Option<Self::Result> {
-        if let Expr::Call { callee, arguments } = expr {
-            if let Some(Object::C(callee)) = self.evaluate(callee) {
-                let mut args = Vec::new();
-                for argument in arguments {
-                    args.push(self.evaluate(argument))
+    fn visit_set_index(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::SetIndex {
+            object,
+            index,
+            value,
+        } = expr
+        {
+            let object = self.evaluate(object)?;
+            let index = self.evaluate(index)?;
+            let value = self.evaluate(value)?;
+            return match object {
+                Object::List(list) => {
+                    let mut list = list.borrow_mut();
+                    let index = list_index(&list, &index)?;
+                    list[index] = value.clone();
+                    Ok(value)
                 }
-                if args.len() != callee.arity() {
-                    panic!(
-                        "wrong number of args {:?} {:?}",
-                        arguments.len(),
-                        callee.arity()
-                    )
+                Object::Map(map) => {
+                    map.borrow_mut().insert(map_key(&index)?, value.clone());
+                    Ok(value)
                 }
-                return callee.call(self, args.into_iter().map(|o| o.unwrap()).collect());
-            }
+                _ => Err(RuntimeError::new("Only lists and maps can be indexed.")),
+            };
         }
         panic!("{:?}", expr)
     }
 }
 
 impl StmtVisitor for Interpreter {
-    fn visit_expr_stmt(&mut self, stmt: &Stmt) {
+    type Result = Result<(), RuntimeError>;
+
+    fn visit_expr_stmt(&mut self, stmt: &Stmt) -> Self::Result {
         if let Stmt::Expr(expr) = stmt {
-            self.evaluate(expr);
+            // The previous value is dropped first so it doesn't hold on to a string being appended to.
+            self.last_value = None;
+            self.last_value = Some(self.evaluate(expr)?);
+            Ok(())
         } else {
             panic!("{:?}", stmt)
         }
     }
 
-    fn visit_print(&mut self, stmt: &Stmt) {
-        if let Stmt::Print(expr) = stmt {
-            match self.evaluate(expr).unwrap() {
-                Object::L(Literal::Float(l)) => println!("{:?}", l),
-                Object::L(Literal::Bool(l)) => println!("{:?}", l),
-                Object::L(Literal::String(l)) => println!("{:?}", l),
-                Object::L(Literal::Nil(l)) => println!("{:?}", l),
-                Object::C(c) => println!("{}", c),
+    fn visit_print(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Print(exprs) = stmt {
+            let mut values = Vec::new();
+            for expr in exprs {
+                values.push(stringify(&self.evaluate(expr)?));
             }
+            writeln!(self.output, "{}", values.join(" "))
+                .map_err(|e| RuntimeError::new(&e.to_string()))?;
+            Ok(())
         } else {
             panic!("{:?}", stmt)
         }
     }
 
-    fn visit_var_decl(&mut self, stmt: &Stmt) {
-        if let Stmt::Variable { name, initializer } = stmt {
-            if let lexer::Token::Identifier(name) = name {
-                let initializer = initializer.as_ref();
-                let mut value = Object::L(Literal::Nil(()));
-                if *initializer != Expr::L(Literal::Nil(())) {
-                    value = self.evaluate(initializer).unwrap();
-                }
+    fn visit_var_decl(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Variable {
+            name: lexer::Token::Identifier(name),
+            initializer,
+        } = stmt
+        {
+            let value = match initializer {
+                Some(initializer) => self.evaluate(initializer)?,
+                None => Object::L(Literal::Nil(())),
+            };
 
-                self.symbol_table.borrow_mut().define(name, value);
-                return;
-            }
+            self.symbol_table.borrow_mut().define(*name, value);
+            return Ok(());
         }
         panic!("{:?}", stmt)
     }
 
-    fn visit_block(&mut self, stmt: &Stmt) {
+    // Unlike a block, a var list defines its names in the current scope.
+    fn visit_var_list(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::VarList(stmts) = stmt {
+            return stmts.iter().try_for_each(|stmt| self.execute(stmt));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_const(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Const {
+            name: lexer::Token::Identifier(name),
+            initializer,
+        } = stmt
+        {
+            let value = self.evaluate(initializer)?;
+            self.symbol_table.borrow_mut().define_const(*name, value);
+            return Ok(());
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_block(&mut self, stmt: &Stmt) -> Self::Result {
         if let Stmt::Block(stmts) = stmt {
             self.execute_block(
                 stmts,
                 SymbolTable {
                     values: Default::default(),
                     enclosing: Some(self.symbol_table.clone()),
+                    constants: Default::default(),
                 },
-            );
+            )?;
         }
+        Ok(())
     }
 
-    fn visit_if(&mut self, stmt: &Stmt) {
+    fn visit_if(&mut self, stmt: &Stmt) -> Self::Result {
         if let Stmt::If {
             condition,
             then_branch,
             else_branch,
         } = stmt
         {
-            if let Some(Object::L(Literal::Bool(true))) = self.evaluate(condition) {
+            if is_truthy(&self.evaluate(condition)?) {
                 self.execute(then_branch)
             } else if let Some(else_branch) = else_branch {
                 self.execute(else_branch)
+            } else {
+                Ok(())
             }
         } else {
             panic!("{:?}", stmt);
         }
     }
 
-    fn visit_while(&mut self, stmt: &Stmt) {
+    fn visit_while(&mut self, stmt: &Stmt) -> Self::Result {
         if let Stmt::While { condition, body } = stmt {
-            while let Some(Object::L(Literal::Bool(true))) = self.evaluate(condition) {
-                self.execute(body);
+            let mut iterations = 0;
+            while is_truthy(&self.evaluate(condition)?) {
+                iterations += 1;
+                if self.loop_limit.is_some_and(|limit| iterations > limit) {
+                    return Err(RuntimeError::new("Loop iteration limit exceeded."));
+                }
+                self.execute(body)?;
+                if self.ret.is_some() {
+                    break;
+                }
             }
+            Ok(())
         } else {
             panic!("{:?}", stmt);
         }
     }
 
-    fn visit_function_decl(&mut self, stmt: &Stmt) {
-        if let Stmt::Function {
-            name, parameters, ..
+    // Case values are evaluated in order only until one matches, and the chosen body gets its own
+    // scope like a block.
+    fn visit_switch(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Switch {
+            subject,
+            cases,
+            default,
         } = stmt
         {
-            if let lexer::Token::Identifier(name) = name {
-                let mut f = callable::Function {
-                    declaration: stmt.clone(),
-                    closure: self.symbol_table.borrow().deep_copy(),
-                };
-                f.closure.define(name, Object::C(Rc::new(f.clone())));
-                self.symbol_table
-                    .borrow_mut()
-                    .define(name, Object::C(Rc::new(f)));
+            let subject = self.evaluate(subject)?;
+            let mut chosen = default.as_ref();
+            for (value, body) in cases {
+                if self.evaluate(value)? == subject {
+                    chosen = Some(body);
+                    break;
+                }
+            }
+            if let Some(body) = chosen {
+                self.execute_block(
+                    body,
+                    SymbolTable {
+                        values: Default::default(),
+                        enclosing: Some(self.symbol_table.clone()),
+                        constants: Default::default(),
+                    },
+                )?;
             }
+            Ok(())
+        } else {
+            panic!("{:?}", stmt);
+        }
+    }
+
+    fn visit_function_decl(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Function {
+            name: lexer::Token::Identifier(name),
+            ..
+        } = stmt
+        {
+            let f = callable::Function {
+                declaration: stmt.clone(),
+                closure: self.symbol_table.clone(),
+            };
+            self.symbol_table
+                .borrow_mut()
+                .define(*name, Object::C(Rc::new(f)));
+            Ok(())
         } else {
             panic!("{:?}", stmt);
         }
     }
 
-    fn visit_return(&mut self, stmt: &Stmt) {
+    // A `return f(...)` of a Lox function inside a function body is handed back to the caller's
+    // Function::call loop instead of recursing, so any tail call runs in constant Rust stack.
+    // Only a call that is the whole return value counts; `return (f())` or `return f() + 1` don't.
+    fn visit_return(&mut self, stmt: &Stmt) -> Self::Result {
         if let Stmt::Return(r) = stmt {
-            match r {
+            match r.as_deref() {
                 None => self.ret = Some(Left(())),
-                Some(r) => self.ret = Some(Right(self.evaluate(r).unwrap())),
+                Some(Expr::Call {
+                    callee, arguments, ..
+                }) if self.depth > 0 => {
+                    let (callee, args) = self.callee_and_args(callee, arguments)?;
+                    match callee.as_function() {
+                        Some(function) => {
+                            self.tail_call = Some((function.clone(), args));
+                            self.ret = Some(Left(()));
+                        }
+                        None => self.ret = Some(Right(callee.call(self, args)?)),
+                    }
+                }
+                Some(r) => self.ret = Some(Right(self.evaluate(r)?)),
             }
+            Ok(())
+        } else {
+            panic!("{:?}", stmt)
+        }
+    }
+
+    fn visit_import(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Import(file) = stmt {
+            // Errors from the imported file keep their own message and line, prefixed with the
+            // file they came from. The line goes into the message, since the reporter would
+            // otherwise quote that line from the importing file.
+            let error = |message: &dyn std::fmt::Display| {
+                RuntimeError::new(&format!("Could not import '{}': {}", file, message))
+            };
+            let path = self
+                .base_dir
+                .join(file)
+                .canonicalize()
+                .map_err(|e| error(&e))?;
+            if !self.imported.insert(path.clone()) {
+                return Ok(());
+            }
+            let source: Vec<char> = fs::read_to_string(&path)
+                .map_err(|e| error(&e))?
+                .chars()
+                .collect();
+            let (tokens, positions) = lexer::lex_with_positions(&source).map_err(|e| error(&e))?;
+            let stmts = Parser::with_positions(tokens, positions)
+                .parse()
+                .map_err(|errors| {
+                    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                    error(&messages.join("; "))
+                })?;
+
+            let base_dir = std::mem::replace(
+                &mut self.base_dir,
+                path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            );
+            let environment = std::mem::replace(&mut self.symbol_table, self.globals.clone());
+            let result = self.interpret(&stmts);
+            self.symbol_table = environment;
+            self.base_dir = base_dir;
+            result.map_err(|e| RuntimeError::new(&format!("In '{}': {}", file, e)))
         } else {
             panic!("{:?}", stmt)
         }
@@ -299,23 +861,77 @@ impl StmtVisitor for Interpreter {
 }
 
 #[cfg(test)]
-mod tests {
-    use crate::interpreter::Interpreter;
-    use crate::lexer::{lexer, Operator, Token};
+pub(crate) mod tests {
+    use crate::intern::Symbol;
+    use crate::interpreter::error::RuntimeError;
+    use crate::interpreter::{stringify, Interpreter, STACK_SIZE};
+    use crate::lexer::{lex, Operator, Token};
     use crate::parser::ast::{Expr, Literal, Stmt};
     use crate::parser::parser::Parser;
+    use crate::symbol_table::Object;
+    use crate::sync::{Rc, RefCell};
+    use std::io;
+    use std::io::Write;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn printed(source: &str) -> String {
+        let output = Buffer::default();
+        Interpreter::new()
+            .with_output(Box::new(output.clone()))
+            .interpret(&parse(source))
+            .unwrap();
+        let bytes = output.0.borrow().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    pub(crate) fn parse(source: &str) -> Vec<Stmt> {
+        let input: Vec<char> = source.chars().collect();
+        let tokens = lex(&input).unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    pub(crate) fn run(source: &str) -> Result<(), RuntimeError> {
+        Interpreter::new().interpret(parse(source).as_ref())
+    }
+
+    pub(crate) fn global(i: &Interpreter, name: &str) -> Object {
+        i.globals.borrow().get(name).unwrap()
+    }
+
+    pub(crate) fn expression(source: &str) -> Expr {
+        let input: Vec<char> = source.chars().collect();
+        let tokens = lex(&input).unwrap();
+        Parser::new(tokens).expression().unwrap()
+    }
+
+    pub(crate) fn eval(source: &str) -> Result<Object, RuntimeError> {
+        Interpreter::new().evaluate(&expression(source))
+    }
 
     #[test]
     fn string() {
-        let x = String::from("x");
-        let y = String::from("y");
+        let x = Rc::new("x".to_string());
+        let y = Rc::new("y".to_string());
         let expr = Box::new(Expr::Binary {
             left: Box::new(Expr::L(Literal::String(x))),
             operator: Operator::Plus,
             right: Box::new(Expr::L(Literal::String(y))),
+            position: None,
         });
 
-        Interpreter::new().interpret(&[Stmt::Expr(expr)]);
+        Interpreter::new().interpret(&[Stmt::Expr(expr)]).unwrap();
 
         let x = 1.0;
         let y = 2.0;
@@ -323,45 +939,61 @@ mod tests {
             left: Box::new(Expr::L(Literal::Float(x))),
             operator: Operator::And,
             right: Box::new(Expr::L(Literal::Float(y))),
+            position: None,
         });
 
-        Interpreter::new().interpret(&[Stmt::Expr(expr)]);
+        Interpreter::new().interpret(&[Stmt::Expr(expr)]).unwrap();
     }
 
     #[test]
     fn interpret_var_stmt() {
         let mut i = Interpreter::new();
 
-        let name = Token::Identifier("z".to_string());
+        let name = Token::Identifier(Symbol::intern("z"));
         let st = Stmt::Variable {
             name: name.clone(),
-            initializer: Box::new(Expr::L(Literal::String("this is z".to_string()))),
+            initializer: Some(Box::new(Expr::L(Literal::String(
+                "this is z".to_string().into(),
+            )))),
         };
-        i.interpret(&[st]);
+        i.interpret(&[st]).unwrap();
 
-        let print = Stmt::Print(Box::from(Expr::Variable { name: name.clone() }));
-        i.interpret(&[print]);
+        let print = Stmt::Print(vec![Expr::Variable {
+            name: name.clone(),
+            position: None,
+        }]);
+        i.interpret(&[print]).unwrap();
 
         let st = Stmt::Variable {
             name: name.clone(),
-            initializer: Box::new(Expr::L(Literal::Float(1.0))),
+            initializer: Some(Box::new(Expr::L(Literal::Float(1.0)))),
         };
-        i.interpret(&[st]);
-        let print = Stmt::Print(Box::from(Expr::Variable { name: name.clone() }));
-        i.interpret(&[print]);
+        i.interpret(&[st]).unwrap();
+        let print = Stmt::Print(vec![Expr::Variable {
+            name: name.clone(),
+            position: None,
+        }]);
+        i.interpret(&[print]).unwrap();
     }
 
     #[test]
     fn interpret_print_stmt() {
-        let x = String::from("x");
-        let y = String::from("y");
-        let print = Stmt::Print(Box::new(Expr::Binary {
+        let x = Rc::new("x".to_string());
+        let y = Rc::new("y".to_string());
+        let print = Stmt::Print(vec![Expr::Binary {
             left: Box::new(Expr::L(Literal::String(x))),
             operator: Operator::Plus,
             right: Box::new(Expr::L(Literal::String(y))),
-        }));
+            position: None,
+        }]);
 
-        Interpreter::new().interpret(&[print]);
+        Interpreter::new().interpret(&[print]).unwrap();
+    }
+
+    #[test]
+    fn print_several() {
+        assert_eq!(printed(r#"print 1, "two", true;"#), "1 two true\n");
+        assert_eq!(printed("var a = 1; print a, a + 1, [a];"), "1 2 [1]\n");
     }
 
     #[test]
@@ -372,10 +1004,10 @@ mod tests {
         "
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
     }
 
     #[test]
@@ -403,10 +1035,10 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
     }
 
     #[test]
@@ -433,10 +1065,10 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
     }
 
     #[test]
@@ -450,11 +1082,11 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
-        Interpreter::new().interpret(e.as_ref());
+        Interpreter::new().interpret(e.as_ref()).unwrap();
     }
 
     #[test]
@@ -469,11 +1101,11 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:?}", e);
-        Interpreter::new().interpret(e.as_ref());
+        Interpreter::new().interpret(e.as_ref()).unwrap();
     }
 
     #[test]
@@ -486,10 +1118,10 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
     }
 
     #[test]
@@ -505,10 +1137,10 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
     }
 
     #[test]
@@ -521,10 +1153,142 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
+    }
+
+    #[test]
+    fn var_list() {
+        assert_eq!(printed("var a = 1, b = a + 1; print b;"), "2\n");
+        assert_eq!(
+            printed(
+                "{ var a = \"x\", b; print b; } for (var i = 0, j = 3; i < j; i = i + 1) print i;"
+            ),
+            "nil\n0\n1\n2\n"
+        );
+    }
+
+    #[test]
+    fn double_negation() {
+        assert_eq!(printed("print - -5;"), "5\n");
+        assert_eq!(printed("print -(-5);"), "5\n");
+        assert_eq!(printed("var x = 2; print - - -x;"), "-2\n");
+        assert_eq!(printed("print !!nil;"), "false\n");
+    }
+
+    #[test]
+    fn print_numbers() {
+        assert_eq!(printed("print 3;"), "3\n");
+        assert_eq!(printed("print 2.5;"), "2.5\n");
+        assert_eq!(printed("print 0.1 + 0.2;"), "0.30000000000000004\n");
+        assert_eq!(printed("print 1 / 3;"), "0.3333333333333333\n");
+        assert_eq!(printed("print -0.5 * 0;"), "-0\n");
+        assert_eq!(printed("print 10 ** 21;"), "1000000000000000000000\n");
+        assert_eq!(printed("print 5.0;"), "5\n");
+        assert_eq!(printed("print 5.5;"), "5.5\n");
+        assert_eq!(printed("print -0.0;"), "-0\n");
+        assert_eq!(printed("print -5.0 + 0.5;"), "-4.5\n");
+        assert_eq!(
+            printed("print \"a\"; print nil; print true;"),
+            "a\nnil\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn print_callables() {
+        assert_eq!(printed("print clock;"), "<native fn clock>\n");
+        assert_eq!(printed("fun f() {} print f;"), "<fn f>\n");
+        assert_eq!(
+            printed("fun f() {} print [f, len];"),
+            "[<fn f>, <native fn len>]\n"
+        );
+        assert_eq!(stringify(&eval("clock").unwrap()), "<native fn clock>");
+    }
+
+    #[test]
+    fn else_if() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            fun grade(n) {
+                var g = "";
+                if (n > 2) g = g + "a"; else if (n > 1) g = g + "b"; else g = g + "c";
+                return g;
+            }
+            var a = grade(3); var b = grade(2); var c = grade(1);
+            fun dangling(x, y) { var r = 0; if (x) if (y) r = 1; else r = 2; return r; }
+            var d = dangling(true, false); var e = dangling(false, false);
+        "#,
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "a")), "a");
+        assert_eq!(stringify(&global(&i, "b")), "b");
+        assert_eq!(stringify(&global(&i, "c")), "c");
+        assert_eq!(stringify(&global(&i, "d")), "2");
+        assert_eq!(stringify(&global(&i, "e")), "0");
+    }
+
+    #[test]
+    fn while_condition_reevaluated() {
+        assert_eq!(
+            printed("var i = 0; while (i < 3) { i = i + 1; } print i;"),
+            "3\n"
+        );
+        assert_eq!(
+            printed(
+                "var calls = 0;
+                 fun more() { calls = calls + 1; return calls < 4; }
+                 while (more()) {}
+                 print calls;"
+            ),
+            "4\n"
+        );
+    }
+
+    #[test]
+    fn for_scope() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            var seen = "";
+            for (var i = 0; i < 3; i = i + 1) seen = seen + str(i) + " ";
+            var nested = "";
+            for (var i = 0; i < 2; i = i + 1) {
+                var i = 10;
+                nested = nested + str(i) + " ";
+            }
+        "#,
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "seen")), "0 1 2 ");
+        assert_eq!(stringify(&global(&i, "nested")), "10 10 ");
+        assert!(i.globals.borrow().get("i").is_err());
+    }
+
+    #[test]
+    fn for_without_braces() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            var total = 0;
+            for (var i = 0; i < 5; i = i + 1) total = total + i;
+            var last = 0;
+            for (var i = 0; i < 3; i = i + 1) if (i > 0) last = i;
+            var n = 0;
+            for (; n < 4;) n = n + 1;
+            var m = 0;
+            fun count() { for (;;) if (m == 7) return; else m = m + 1; }
+            count();
+        "#,
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "total")), "10");
+        assert_eq!(stringify(&global(&i, "last")), "2");
+        assert_eq!(stringify(&global(&i, "n")), "4");
+        assert_eq!(stringify(&global(&i, "m")), "7");
+        assert!(i.globals.borrow().get("i").is_err());
     }
 
     #[test]
@@ -537,10 +1301,10 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
     }
 
     #[test]
@@ -555,10 +1319,83 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
+    }
+
+    #[test]
+    fn nil_return() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            fun f() { return; }
+            fun g() {}
+            fun h(x) { if (x) { return; } return x; }
+            var a = f();
+            var b = g();
+            var c = h(true);
+            var d = f() or "fallback";
+            var e = g() and "unreachable";
+        "#,
+        ))
+        .unwrap();
+        for name in &["a", "b", "c", "e"] {
+            assert!(matches!(global(&i, name), Object::L(Literal::Nil(()))));
+        }
+        assert_eq!(stringify(&global(&i, "d")), "fallback");
+    }
+
+    #[test]
+    fn print_nil_return() {
+        assert_eq!(printed("fun f() { return; } print f();"), "nil\n");
+        assert_eq!(printed("fun g() {} print g();"), "nil\n");
+    }
+
+    #[test]
+    fn truthy_conditions() {
+        let source = r#"
+            if (1 and 2) print "then"; else print "else";
+            if (nil) print "then"; else print "else";
+            var i = 0;
+            while (i < 3 and "y") i = i + 1;
+            print i;
+            var s = "";
+            while (s and len(s) < 2) s = s + "x";
+            print s;
+        "#;
+        assert_eq!(printed(source), "then\nelse\n3\nxx\n");
+    }
+
+    #[test]
+    fn coalesce() {
+        assert_eq!(stringify(&eval("nil ?? 5").unwrap()), "5");
+        assert_eq!(stringify(&eval("3 ?? 5").unwrap()), "3");
+        assert_eq!(stringify(&eval("false ?? 5").unwrap()), "false");
+        assert_eq!(stringify(&eval("nil ?? nil ?? 7").unwrap()), "7");
+        assert_eq!(stringify(&eval("3 ?? undefinedVariable").unwrap()), "3");
+        assert_eq!(stringify(&eval("false or nil ?? 1").unwrap()), "1");
+    }
+
+    #[test]
+    fn arrow_function() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            fun sq(x) => x * x;
+            var y = 3;
+            fun addY(x) => x + y;
+            fun fact(n) => n < 2 and 1 or n * fact(n - 1);
+            var a = sq(4);
+            var b = addY(1);
+            var c = fact(5);
+        "#,
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "a")), "16");
+        assert_eq!(stringify(&global(&i, "b")), "4");
+        assert_eq!(stringify(&global(&i, "c")), "120");
     }
 
     #[test]
@@ -574,10 +1411,10 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
     }
 
     #[test]
@@ -592,11 +1429,829 @@ mod tests {
 
             print fibonacci(10);
         "#
-            .chars()
-            .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        .chars()
+        .collect();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
-        Interpreter::new().interpret(e.as_ref());
+        let e = p.parse().unwrap();
+        Interpreter::new().interpret(e.as_ref()).unwrap();
+    }
+
+    #[test]
+    fn call_non_callable() {
+        assert_eq!(
+            run("var x = 1; x();"),
+            Err(RuntimeError::new("Can only call functions and classes."))
+        );
+        assert_eq!(
+            run("\"not a function\"();"),
+            Err(RuntimeError::new("Can only call functions and classes."))
+        );
+    }
+
+    #[test]
+    fn arity_mismatch() {
+        assert_eq!(
+            run("fun f(a) {} f(1, 2);"),
+            Err(RuntimeError::new("Expected 1 arguments but got 2."))
+        );
+        assert_eq!(
+            run("len();"),
+            Err(RuntimeError::new("Expected 1 arguments but got 0."))
+        );
+        // The same check applies to a tail call.
+        assert_eq!(
+            run("fun f(a) {} fun g() { return f(); } g();"),
+            Err(RuntimeError::new("Expected 1 arguments but got 0."))
+        );
+    }
+
+    #[test]
+    fn undefined_variable() {
+        assert_eq!(
+            run("print undefinedVar;"),
+            Err(RuntimeError::new("Undefined variable 'undefinedVar'."))
+        );
+        assert_eq!(
+            run("var a = undefinedVar;"),
+            Err(RuntimeError::new("Undefined variable 'undefinedVar'."))
+        );
+    }
+
+    #[test]
+    fn assign_undefined_variable() {
+        assert_eq!(
+            run("x = 1;"),
+            Err(RuntimeError::new("Undefined variable 'x'."))
+        );
+        assert_eq!(
+            run("{ y = 1; }"),
+            Err(RuntimeError::new("Undefined variable 'y'."))
+        );
+        assert_eq!(run("var x = 0; x = 1; print x;"), Ok(()));
+    }
+
+    #[test]
+    fn unary() {
+        let mut i = Interpreter::new();
+        let mut not = |right: Literal| {
+            i.evaluate(&Expr::Unary {
+                operator: Operator::Not,
+                right: Box::new(Expr::L(right)),
+                position: None,
+            })
+        };
+        assert!(matches!(
+            not(Literal::Nil(())),
+            Ok(Object::L(Literal::Bool(true)))
+        ));
+        assert!(matches!(
+            not(Literal::String("x".to_string().into())),
+            Ok(Object::L(Literal::Bool(false)))
+        ));
+        assert!(matches!(
+            not(Literal::Bool(false)),
+            Ok(Object::L(Literal::Bool(true)))
+        ));
+
+        assert_eq!(run("print -5;"), Ok(()));
+        assert_eq!(
+            run("print -\"x\";"),
+            Err(RuntimeError::new("Operand must be a number."))
+        );
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(
+            run("print 1 / 0;"),
+            Err(RuntimeError::new("Division by zero."))
+        );
+        assert!(matches!(
+            eval("6 / 4"),
+            Ok(Object::L(Literal::Float(f))) if f == 1.5
+        ));
+    }
+
+    #[test]
+    fn bitwise() {
+        let int = |source: &str| match eval(source) {
+            Ok(Object::L(Literal::Float(f))) => f,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(int("6 & 3"), 2.0);
+        assert_eq!(int("6 | 1"), 7.0);
+        assert_eq!(int("5 ^ 1"), 4.0);
+        assert_eq!(int("1 << 4"), 16.0);
+        assert_eq!(int("256 >> 4"), 16.0);
+        assert_eq!(int("1 | 2 ^ 3 & 4"), 3.0);
+        assert_eq!(int("1 + 1 << 1"), 4.0);
+        assert_eq!(int("0xFF & 0b1010"), 10.0);
+        assert_eq!(int("0xFFFFFFFF & 0x80000000"), 2147483648.0);
+        assert_eq!(
+            eval("1.5 & 1").unwrap_err(),
+            RuntimeError::new("Operands must be integers.")
+        );
+        assert_eq!(
+            eval("\"a\" | 1").unwrap_err(),
+            RuntimeError::new("Operands must be integers.")
+        );
+        assert_eq!(
+            eval("1 << -1").unwrap_err(),
+            RuntimeError::new("Shift amount out of range.")
+        );
+    }
+
+    #[test]
+    fn compound_assign() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            var a = 10; a += 5;
+            var b = 10; b -= 4;
+            var c = 10; c *= 3;
+            var d = 10; d /= 4;
+            var s = "con"; s += "cat";
+        "#,
+        ))
+        .unwrap();
+        let num = |name: &str| match global(&i, name) {
+            Object::L(Literal::Float(f)) => f,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(num("a"), 15.0);
+        assert_eq!(num("b"), 6.0);
+        assert_eq!(num("c"), 30.0);
+        assert_eq!(num("d"), 2.5);
+        assert!(matches!(
+            global(&i, "s"),
+            Object::L(Literal::String(s)) if &*s == "concat"
+        ));
+    }
+
+    #[test]
+    fn increment() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            var a = 1;
+            var old = a++;
+            var new = ++a;
+            var b = 5;
+            var c = b--;
+            --b;
+        "#,
+        ))
+        .unwrap();
+        let num = |name: &str| match global(&i, name) {
+            Object::L(Literal::Float(f)) => f,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(num("old"), 1.0);
+        assert_eq!(num("new"), 3.0);
+        assert_eq!(num("a"), 3.0);
+        assert_eq!(num("c"), 5.0);
+        assert_eq!(num("b"), 3.0);
+
+        // The old value is kept rather than recomputed, so fractions and large numbers are exact.
+        assert_eq!(printed("var a = 0.1; print a++; print a;"), "0.1\n1.1\n");
+        assert_eq!(
+            printed("var a = 10000000000000000; print a++;"),
+            "10000000000000000\n"
+        );
+    }
+
+    #[test]
+    fn list() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            var empty = [];
+            var l = [1, "two", 1 + 2];
+            var first = l[0];
+            l[1] = 2;
+            var alias = l;
+            alias[2] = l[0] + l[1];
+            var nested = [[1, 2], [3]];
+            var inner = nested[0][1];
+        "#,
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "empty")), "[]");
+        assert_eq!(stringify(&global(&i, "first")), "1");
+        assert_eq!(stringify(&global(&i, "l")), "[1, 2, 3]");
+        assert_eq!(stringify(&global(&i, "inner")), "2");
+
+        assert_eq!(
+            run("var l = [1, 2]; print l[2];"),
+            Err(RuntimeError::new("Index out of bounds."))
+        );
+        assert_eq!(
+            run("var l = [1, 2]; l[-1] = 0;"),
+            Err(RuntimeError::new("Index out of bounds."))
+        );
+        assert_eq!(
+            run("var l = [1, 2]; print l[0.5];"),
+            Err(RuntimeError::new("List index must be an integer."))
+        );
+        assert_eq!(
+            run("var n = 1; print n[0];"),
+            Err(RuntimeError::new("Only lists and maps can be indexed."))
+        );
+    }
+
+    #[test]
+    fn map() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            var empty = {};
+            var m = {"a": 1, "b": 1 + 1};
+            var a = m["a"];
+            m["c"] = 3;
+            m["a"] = "one";
+            var missing = m["z"];
+        "#,
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "empty")), "{}");
+        assert_eq!(stringify(&global(&i, "a")), "1");
+        assert_eq!(stringify(&global(&i, "m")), "{a: one, b: 2, c: 3}");
+        assert_eq!(stringify(&global(&i, "missing")), "nil");
+
+        assert_eq!(
+            run("var m = {num(\"NaN\"): 2};"),
+            Err(RuntimeError::new("NaN can't be used as a map key."))
+        );
+    }
+
+    #[test]
+    fn map_keys() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            var m = {1: "one", "1": "string", true: "yes", nil: "nothing"};
+            m[2] = "two";
+            m[-0] = "zero";
+            var one = m[1];
+            var oneStr = m["1"];
+            var yes = m[true];
+            var two = m[1 + 1];
+            var zero = m[0];
+            var nothing = m[nil];
+            var missing = m[false];
+        "#,
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "one")), "one");
+        assert_eq!(stringify(&global(&i, "oneStr")), "string");
+        assert_eq!(stringify(&global(&i, "yes")), "yes");
+        assert_eq!(stringify(&global(&i, "two")), "two");
+        assert_eq!(stringify(&global(&i, "zero")), "zero");
+        assert_eq!(stringify(&global(&i, "nothing")), "nothing");
+        assert_eq!(stringify(&global(&i, "missing")), "nil");
+    }
+
+    #[test]
+    fn register_native() {
+        let mut i = Interpreter::new();
+        i.register_native(
+            "double",
+            1,
+            Rc::new(|_, arguments| match arguments[0] {
+                Object::L(Literal::Float(f)) => Object::L(Literal::Float(f * 2.0)),
+                _ => Object::L(Literal::Nil(())),
+            }),
+        );
+        i.interpret(&parse("var d = double(21); var n = double(\"x\");"))
+            .unwrap();
+        assert_eq!(stringify(&global(&i, "d")), "42");
+        assert_eq!(stringify(&global(&i, "n")), "nil");
+        assert_eq!(stringify(&global(&i, "double")), "<native fn double>");
+    }
+
+    #[test]
+    fn short_circuit_skips_calls() {
+        let calls = |source: &str| {
+            let mut i = Interpreter::new();
+            i.interpret(&parse("var calls = 0;")).unwrap();
+            i.register_native(
+                "incr",
+                0,
+                Rc::new(|interpreter, _| {
+                    let mut globals = interpreter.globals.borrow_mut();
+                    if let Ok(Object::L(Literal::Float(n))) = globals.get("calls") {
+                        globals
+                            .assign("calls", Object::L(Literal::Float(n + 1.0)))
+                            .unwrap();
+                    }
+                    Object::L(Literal::Bool(true))
+                }),
+            );
+            i.interpret(&parse(source)).unwrap();
+            stringify(&global(&i, "calls"))
+        };
+        assert_eq!(calls("false and incr();"), "0");
+        assert_eq!(calls("true or incr();"), "0");
+        assert_eq!(calls("1 ?? incr();"), "0");
+        assert_eq!(calls("true and incr();"), "1");
+        assert_eq!(calls("false or incr();"), "1");
+        assert_eq!(calls("nil ?? incr();"), "1");
+        assert_eq!(calls("incr() or incr() and incr();"), "1");
+    }
+
+    #[test]
+    fn import() {
+        let dir = std::env::temp_dir().join(format!("lox_import_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("lib.lox"),
+            "import \"main.lox\"; fun square(x) { return x * x; }",
+        )
+        .unwrap();
+        let main = dir.join("main.lox");
+        let source = "import \"lib.lox\"; import \"lib.lox\"; var result = square(3);";
+        std::fs::write(&main, source).unwrap();
+
+        let mut i = Interpreter::new().with_script(&main);
+        i.interpret(&parse(source)).unwrap();
+        assert_eq!(stringify(&global(&i, "result")), "9");
+        assert!(run("import \"missing.lox\";")
+            .unwrap_err()
+            .message
+            .starts_with("Could not import 'missing.lox': "));
+
+        std::fs::write(dir.join("bad.lox"), "print 1;\nvar = 2;").unwrap();
+        std::fs::write(dir.join("fails.lox"), "var a = 1;\nprint -\"a\";").unwrap();
+        let mut i = Interpreter::new().with_script(&main);
+        assert_eq!(
+            i.interpret(&parse("import \"bad.lox\";")),
+            Err(RuntimeError::new(
+                "Could not import 'bad.lox': [line 2] Expect variable name, found Assign"
+            ))
+        );
+        assert_eq!(
+            i.interpret(&parse("import \"fails.lox\";")),
+            Err(RuntimeError::new(
+                "In 'fails.lox': [line 2] Operand must be a number."
+            ))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    const FIBONACCI: &str = r#"
+        fun fib(n) {
+            if (n < 2) return n;
+            return fib(n - 1) + fib(n - 2);
+        }
+        var result = fib(20);
+    "#;
+
+    #[test]
+    fn fibonacci_result() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(FIBONACCI)).unwrap();
+        assert_eq!(stringify(&global(&i, "result")), "6765");
+    }
+
+    // Wall-clock bounds are unreliable on a loaded machine, so this runs only when asked for with
+    // `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn fibonacci_timing() {
+        let start = std::time::Instant::now();
+        Interpreter::new().interpret(&parse(FIBONACCI)).unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn closure_shares_scope() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            fun makeCounter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            var counter = makeCounter();
+            counter();
+            var count = counter();
+            "#,
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "count")), "2");
+    }
+
+    #[test]
+    fn nested_function_sees_outer_locals() {
+        let source = r#"
+            fun outer() {
+                var x = 10;
+                fun inner() { return x; }
+                return inner();
+            }
+            print outer();
+        "#;
+        assert_eq!(printed(source), "10\n");
+    }
+
+    #[test]
+    fn var_without_initializer() {
+        let source = r#"
+            fun f() { print "called"; }
+            var a;
+            var b = nil;
+            var c = f();
+            print a, b, c;
+        "#;
+        assert_eq!(printed(source), "called\nnil nil nil\n");
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(printed("const PI = 3.14159; print PI * 2;"), "6.28318\n");
+        for source in &[
+            "const PI = 3.14159; PI = 3;",
+            "const PI = 3.14159; PI += 1;",
+            "const PI = 3.14159; { PI = 3; }",
+            "const PI = 3.14159; fun f() { PI++; } f();",
+        ] {
+            assert_eq!(
+                run(source).unwrap_err().message,
+                "Cannot assign to const 'PI'."
+            );
+        }
+        // A var in an inner scope shadows the const, and redeclaring it with var replaces it.
+        assert_eq!(
+            printed("const k = 1; { var k = 2; k = 3; print k; } print k;"),
+            "3\n1\n"
+        );
+        assert!(run("const k = 1; var k = 2; k = 3;").is_ok());
+    }
+
+    #[test]
+    fn chained_calls_and_indexes() {
+        // There are no classes yet, so `.name` can't appear in a chain, but calls and indexes
+        // interleave freely and run left to right.
+        let source = r#"
+            var trace = "";
+            fun a() { trace = trace + "a"; return [b]; }
+            fun b() { trace = trace + "b"; return {"c": c}; }
+            fun c(x) { trace = trace + x; return trace; }
+            print a()[0]()["c"]("!");
+        "#;
+        assert_eq!(printed(source), "ab!\n");
+    }
+
+    #[test]
+    fn mutual_recursion() {
+        // isEven's closure is the live global scope, so it sees isOdd even though isOdd is
+        // declared after it.
+        let source = r#"
+            fun isEven(n) {
+                if (n == 0) return true;
+                return isOdd(n - 1);
+            }
+            fun isOdd(n) {
+                if (n == 0) return false;
+                return isEven(n - 1);
+            }
+            print isEven(4), isOdd(4), isEven(3), isOdd(3);
+        "#;
+        assert_eq!(printed(source), "true false false true\n");
+    }
+
+    #[test]
+    fn call_returned_function() {
+        let source = r#"
+            fun identity(a) { return a; }
+            fun addPair(a, b) { return a + b; }
+            print identity(addPair)(1, 2);
+            fun adder(a) {
+                fun add(b) { return a + b; }
+                return add;
+            }
+            print adder(1)(2), identity(identity)(adder)(3)(4);
+        "#;
+        assert_eq!(printed(source), "3\n3 7\n");
+    }
+
+    #[test]
+    fn tail_call() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            fun countdown(n, acc) {
+                if (n == 0) return acc;
+                return countdown(n - 1, acc + 1);
+            }
+            fun isEven(n) {
+                if (n == 0) return true;
+                return isOdd(n - 1);
+            }
+            fun isOdd(n) {
+                if (n == 0) return false;
+                return isEven(n - 1);
+            }
+            fun length(s) { return len(s); }
+            var count = countdown(100000, 0);
+            var even = isEven(10001);
+            var l = length("abc");
+            "#,
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "count")), "100000");
+        assert_eq!(stringify(&global(&i, "even")), "false");
+        assert_eq!(stringify(&global(&i, "l")), "3");
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let source = "fun f(n) { return 1 + f(n + 1); } f(0);";
+        let result = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || run(source))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(result, Err(RuntimeError::new("Stack overflow.")));
+
+        let mut i = Interpreter::new().with_max_depth(10);
+        i.interpret(&parse(
+            "fun depth(n) { if (n == 0) return 0; return 1 + depth(n - 1); } var d = depth(9);",
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "d")), "9");
+        assert_eq!(
+            i.interpret(&parse("depth(10);")),
+            Err(RuntimeError::new("Stack overflow."))
+        );
+        i.interpret(&parse("d = depth(5);")).unwrap();
+        assert_eq!(stringify(&global(&i, "d")), "5");
+    }
+
+    #[test]
+    fn loop_limit() {
+        let mut i = Interpreter::new().with_loop_limit(5);
+        i.interpret(&parse(
+            "var n = 0; while (n < 5) n = n + 1; for (var i = 0; i < 3; i = i + 1) n = n + 1;",
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "n")), "8");
+        assert_eq!(
+            i.interpret(&parse("while (true) {}")),
+            Err(RuntimeError::new("Loop iteration limit exceeded."))
+        );
+        assert_eq!(
+            i.interpret(&parse("n = 0; while (n < 6) n = n + 1;")),
+            Err(RuntimeError::new("Loop iteration limit exceeded."))
+        );
+        assert_eq!(stringify(&global(&i, "n")), "5");
+    }
+
+    #[test]
+    fn equality() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            var nan = num("NaN");
+            var nanEq = nan == nan;
+            var nanNe = nan != nan;
+            var nanLt = nan < 1;
+            var zeroEq = -0.0 == 0.0;
+            var branch = "else";
+            if (nan == nan) branch = "then"; else branch = "else";
+            var strEq = "a" == "a";
+            var mixed = 1 == "1";
+            var nilEq = nil == nil;
+            var list = [1];
+            var listEq = list == list;
+            var listNe = [1] == [1];
+            "#,
+        ))
+        .unwrap();
+        for (name, value) in &[
+            ("nanEq", "false"),
+            ("nanNe", "true"),
+            ("nanLt", "false"),
+            ("zeroEq", "true"),
+            ("branch", "else"),
+            ("strEq", "true"),
+            ("mixed", "false"),
+            ("nilEq", "true"),
+            ("listEq", "true"),
+            ("listNe", "false"),
+        ] {
+            assert_eq!(stringify(&global(&i, name)), *value, "{}", name);
+        }
+    }
+
+    #[test]
+    fn session() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"var n = 42; var s = "hi"; var b = true; fun f() {} var l = [1];"#,
+        ))
+        .unwrap();
+        let json = i.dump_globals();
+        assert_eq!(
+            json,
+            "{\n  \"b\": true,\n  \"n\": 42,\n  \"s\": \"hi\"\n}\n"
+        );
+
+        let mut fresh = Interpreter::new();
+        fresh.load_globals(&json).unwrap();
+        fresh.interpret(&parse("var t = s + str(n);")).unwrap();
+        assert_eq!(stringify(&global(&fresh, "t")), "hi42");
+        assert_eq!(stringify(&global(&fresh, "b")), "true");
+        assert!(fresh.globals.borrow().get("f").is_err());
+    }
+
+    // Every number, hex and binary literals included, is an f64 at runtime, so there's no i32
+    // arithmetic that could overflow.
+    #[test]
+    fn no_integer_overflow() {
+        let number = |source| stringify(&eval(source).unwrap());
+        assert_eq!(number("2147483647 + 1"), "2147483648");
+        assert_eq!(number("0x7fffffff + 1"), "2147483648");
+        assert_eq!(number("-2147483647 - 2"), "-2147483649");
+        assert_eq!(number("65536 * 65536"), "4294967296");
+    }
+
+    #[test]
+    fn power() {
+        let power = |source| stringify(&eval(source).unwrap());
+        assert_eq!(power("2 ** 10"), "1024");
+        assert_eq!(power("2 ** 3 ** 2"), "512");
+        assert_eq!(power("-2 ** 2"), "-4");
+        assert_eq!(power("(-2) ** 2"), "4");
+        assert_eq!(power("2 ** -1"), "0.5");
+        assert_eq!(power("2 * 3 ** 2"), "18");
+    }
+
+    #[test]
+    fn string_repetition() {
+        assert_eq!(stringify(&eval("\"ab\" * 3").unwrap()), "ababab");
+        assert_eq!(stringify(&eval("\"x\" * 0").unwrap()), "");
+        assert_eq!(stringify(&eval("\"a\" + \"b\" * 2").unwrap()), "abb");
+        assert_eq!(stringify(&eval("2 * 3").unwrap()), "6");
+        for source in &["\"x\" * -1", "\"x\" * 1.5"] {
+            assert_eq!(
+                eval(source).unwrap_err().message,
+                "String repetition count must be a non-negative integer."
+            );
+        }
+        for source in &[
+            "\"a\" * 100000000000000",
+            "\"ab\" * 10 ** 300",
+            "\"\" * 10 ** 300",
+        ] {
+            assert_eq!(
+                eval(source).unwrap_err().message,
+                "String repetition result is too long."
+            );
+        }
+        assert_eq!(stringify(&eval("\"\" * 1000000").unwrap()), "");
+    }
+
+    #[test]
+    fn last_value() {
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&parse("1 + 1;")).unwrap();
+        assert!(matches!(
+            interpreter.take_last_value(),
+            Some(Object::L(Literal::Float(f))) if f == 2.0
+        ));
+        assert!(interpreter.take_last_value().is_none());
+
+        interpreter.interpret(&parse("3; var a = 4;")).unwrap();
+        assert!(matches!(
+            interpreter.take_last_value(),
+            Some(Object::L(Literal::Float(f))) if f == 3.0
+        ));
+        interpreter.interpret(&parse("print 5;")).unwrap();
+        assert!(interpreter.take_last_value().is_none());
+    }
+
+    #[test]
+    fn default_has_natives() {
+        let interpreter = Interpreter::default();
+        let clock = interpreter.globals.borrow().get("clock");
+        assert!(matches!(clock, Ok(Object::C(_))));
+    }
+
+    #[test]
+    fn concat_in_loop() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            r#"
+            var s = "";
+            var n = 0;
+            while (n < 10000) {
+                s = s + "ab";
+                n = n + 1;
+            }
+        "#,
+        ))
+        .unwrap();
+        let string = |i: &Interpreter| match global(i, "s") {
+            Object::L(Literal::String(s)) => s,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(string(&i).len(), 20000);
+
+        // Appending keeps the string where it is. A copy would have to be made while the variable
+        // still held the old string, so it could never land at the same address.
+        let address = Rc::as_ptr(&string(&i)) as usize;
+        for source in &[r#"s = s + "cd";"#, r#"s += "ef";"#] {
+            i.interpret(&parse(source)).unwrap();
+            assert_eq!(Rc::as_ptr(&string(&i)) as usize, address);
+        }
+        assert!(string(&i).ends_with("abcdef"));
+    }
+
+    #[test]
+    fn strings_share_buffers() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(&parse(r#"var s = "shared";"#))
+            .unwrap();
+        let s = interpreter.globals.borrow().get("s").unwrap();
+        let copy = interpreter.globals.borrow().get("s").unwrap();
+        match (s, copy) {
+            (Object::L(Literal::String(a)), Object::L(Literal::String(b))) => {
+                assert!(Rc::ptr_eq(&a, &b))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn operand_types() {
+        for source in &[
+            "nil < 1",
+            "true > 2",
+            "1 <= \"a\"",
+            "[] >= []",
+            "nil - 1",
+            "\"a\" / 2",
+        ] {
+            assert_eq!(
+                eval(source).unwrap_err().message,
+                "Operands must be numbers.",
+                "{}",
+                source
+            );
+        }
+        assert_eq!(
+            eval("nil + 1").unwrap_err().message,
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(stringify(&eval("1 < 2").unwrap()), "true");
+        assert_eq!(stringify(&eval("2 <= 1").unwrap()), "false");
+    }
+
+    #[test]
+    fn string_comparison() {
+        let compare = |source| stringify(&eval(source).unwrap());
+        assert_eq!(compare("\"apple\" < \"banana\""), "true");
+        assert_eq!(compare("\"apple\" > \"banana\""), "false");
+        assert_eq!(compare("\"b\" <= \"b\""), "true");
+        assert_eq!(compare("\"a\" >= \"b\""), "false");
+        assert_eq!(compare("\"Z\" < \"a\""), "true");
+        assert_eq!(compare("\"ab\" > \"a\""), "true");
+        assert_eq!(compare("\"a\" == \"a\""), "true");
+        assert_eq!(compare("\"a\" != \"b\""), "true");
+    }
+
+    #[test]
+    fn switch() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse(
+            "fun pick(x) { var r = \"none\"; switch (x) { case 1: r = \"one\"; case 1 + 1: r = \"two\"; default: r = \"other\"; } return r; }
+             var a = pick(1); var b = pick(2); var c = pick(3);
+             var d = 0; switch (\"s\") { case \"t\": d = 1; } var e = 0; switch (2) { case 2: e = 1; case 2: e = 2; }",
+        ))
+        .unwrap();
+        assert_eq!(stringify(&global(&i, "a")), "one");
+        assert_eq!(stringify(&global(&i, "b")), "two");
+        assert_eq!(stringify(&global(&i, "c")), "other");
+        assert_eq!(stringify(&global(&i, "d")), "0");
+        assert_eq!(stringify(&global(&i, "e")), "1");
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn send_to_thread() {
+        let mut i = Interpreter::new();
+        i.interpret(&parse("fun add(a, b) { return a + b; } var m = {1: [2]};"))
+            .unwrap();
+        let i =
+            std::thread::spawn(move || i.interpret(&parse("var x = add(m[1][0], 3);")).map(|_| i))
+                .join()
+                .unwrap()
+                .unwrap();
+        assert_eq!(stringify(&global(&i, "x")), "5");
     }
 }
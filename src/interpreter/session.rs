@@ -0,0 +1,118 @@
+use crate::interpreter::error::RuntimeError;
+use crate::parser::ast::Literal;
+use pom::parser::{end, is_a, list, none_of, one_of, sym, tag, Parser};
+
+// Sessions are a flat JSON object of literal-valued globals. JSON has no NaN or infinities, so
+// non-finite numbers are left out along with callables, lists and maps.
+pub fn to_json(globals: &[(String, Literal)]) -> String {
+    let members: Vec<String> = globals
+        .iter()
+        .filter_map(|(name, value)| {
+            let value = match value {
+                Literal::Float(f) if f.is_finite() => format!("{}", f),
+                Literal::Float(_) => return None,
+                Literal::Bool(b) => format!("{}", b),
+                Literal::String(s) => escape(s),
+                Literal::Nil(_) => "null".to_string(),
+            };
+            Some(format!("  {}: {}", escape(name), value))
+        })
+        .collect();
+    if members.is_empty() {
+        return "{}\n".to_string();
+    }
+    format!("{{\n{}\n}}\n", members.join(",\n"))
+}
+
+pub fn from_json(json: &str) -> Result<Vec<(String, Literal)>, RuntimeError> {
+    let input: Vec<char> = json.chars().collect();
+    let parser = object();
+    parser
+        .parse(&input)
+        .map_err(|e| RuntimeError::new(&format!("Invalid session: {}", e)))
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            ch if ch.is_control() => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn space<'a>() -> Parser<'a, char, ()> {
+    one_of(" \t\r\n").repeat(0..).discard()
+}
+
+fn string<'a>() -> Parser<'a, char, String> {
+    let escaped = sym('\\')
+        * (sym('"')
+            | sym('\\')
+            | sym('/')
+            | sym('n').map(|_| '\n')
+            | sym('r').map(|_| '\r')
+            | sym('t').map(|_| '\t')
+            | (sym('u') * is_a(|ch: char| ch.is_ascii_hexdigit()).repeat(4)).map(|digits| {
+                let digits: String = digits.into_iter().collect();
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                    .unwrap_or('\u{fffd}')
+            }));
+    (sym('"') * (none_of("\\\"") | escaped).repeat(0..) - sym('"'))
+        .map(|chars| chars.into_iter().collect())
+}
+
+fn number<'a>() -> Parser<'a, char, f64> {
+    is_a(|ch: char| ch.is_ascii_digit() || "+-.eE".contains(ch))
+        .repeat(1..)
+        .convert(|chars| chars.into_iter().collect::<String>().parse::<f64>())
+}
+
+fn value<'a>() -> Parser<'a, char, Literal> {
+    string().map(|s| Literal::String(s.into()))
+        | number().map(Literal::Float)
+        | tag("true").map(|_| Literal::Bool(true))
+        | tag("false").map(|_| Literal::Bool(false))
+        | tag("null").map(|_| Literal::Nil(()))
+}
+
+fn object<'a>() -> Parser<'a, char, Vec<(String, Literal)>> {
+    let member = space() * string() - space() - sym(':') - space() + value() - space();
+    space() * sym('{') * (list(member, sym(',')) - space()) - sym('}') - space() - end()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::session::{from_json, to_json};
+    use crate::parser::ast::Literal;
+
+    #[test]
+    fn round_trip() {
+        let globals = vec![
+            ("a".to_string(), Literal::Float(-1.5)),
+            ("b".to_string(), Literal::Bool(true)),
+            ("c".to_string(), Literal::Nil(())),
+            (
+                "d".to_string(),
+                Literal::String("say \"hi\"\n\t\\ \u{1}".to_string().into()),
+            ),
+        ];
+        assert_eq!(from_json(&to_json(&globals)).unwrap(), globals);
+        assert_eq!(from_json(&to_json(&[])).unwrap(), vec![]);
+        assert_eq!(
+            to_json(&[("n".to_string(), Literal::Float(f64::NAN))]),
+            "{}\n"
+        );
+        assert!(from_json("{\"a\": }").is_err());
+    }
+}
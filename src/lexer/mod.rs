@@ -1,4 +1,8 @@
-use pom::parser::{is_a, none_of, one_of, seq, sym, Parser};
+pub mod error;
+
+use crate::intern::Symbol;
+use crate::lexer::error::LexError;
+use pom::parser::{any, empty, is_a, none_of, one_of, seq, sym, Parser};
 use std::fmt;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -7,15 +11,22 @@ pub enum Operator {
     Plus,
     Slash,
     Star,
+    Power,
     Not,
     And,
     Or,
+    Coalesce,
     NotEqual,
     Equal,
     GreaterThanOrEqual,
     LessThanOrEqual,
     GreaterThan,
     LessThan,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 }
 
 impl fmt::Display for Operator {
@@ -25,15 +36,22 @@ impl fmt::Display for Operator {
             Operator::Plus => write!(f, "+"),
             Operator::Slash => write!(f, "/"),
             Operator::Star => write!(f, "*"),
+            Operator::Power => write!(f, "**"),
             Operator::Not => write!(f, "!"),
             Operator::And => write!(f, "and"),
             Operator::Or => write!(f, "or"),
+            Operator::Coalesce => write!(f, "??"),
             Operator::NotEqual => write!(f, "!="),
             Operator::Equal => write!(f, "=="),
             Operator::GreaterThanOrEqual => write!(f, ">="),
             Operator::LessThanOrEqual => write!(f, "<="),
             Operator::GreaterThan => write!(f, ">"),
             Operator::LessThan => write!(f, "<"),
+            Operator::BitAnd => write!(f, "&"),
+            Operator::BitOr => write!(f, "|"),
+            Operator::BitXor => write!(f, "^"),
+            Operator::ShiftLeft => write!(f, "<<"),
+            Operator::ShiftRight => write!(f, ">>"),
         }
     }
 }
@@ -45,33 +63,45 @@ pub enum Token {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Semicolon,
     Dot,
 
     O(Operator),
 
     Assign,
+    Arrow,
+    CompoundAssign(Operator),
+    Increment,
+    Decrement,
 
     // two character tokens.
     Comment,
 
     String(String),
     Float(f64),
-    Int(i32),
+    Int(u64),
     Bool(bool),
-    Identifier(String),
+    Identifier(Symbol),
     Nil(()),
 
     // Keywords.
+    Case,
     Class,
+    Const,
+    Default,
     Else,
     Fun,
     For,
     If,
+    Import,
     Print,
     Return,
     Super,
+    Switch,
     This,
     Var,
     While,
@@ -87,13 +117,20 @@ impl fmt::Display for Token {
             Token::RightParen => write!(f, ")"),
             Token::LeftBrace => write!(f, "{{"),
             Token::RightBrace => write!(f, "}}"),
+            Token::LeftBracket => write!(f, "["),
+            Token::RightBracket => write!(f, "]"),
             Token::Comma => write!(f, ","),
+            Token::Colon => write!(f, ":"),
             Token::Dot => write!(f, "."),
             Token::Semicolon => write!(f, ";"),
 
             Token::O(o) => write!(f, "{}", o),
 
             Token::Assign => write!(f, "="),
+            Token::Arrow => write!(f, "=>"),
+            Token::CompoundAssign(o) => write!(f, "{}=", o),
+            Token::Increment => write!(f, "++"),
+            Token::Decrement => write!(f, "--"),
             Token::Comment => write!(f, "//"),
 
             Token::String(lit) => write!(f, "{}", lit),
@@ -104,14 +141,19 @@ impl fmt::Display for Token {
 
             Token::Nil(_) => write!(f, "nil"),
 
+            Token::Case => write!(f, "case"),
             Token::Class => write!(f, "class"),
+            Token::Const => write!(f, "const"),
+            Token::Default => write!(f, "default"),
             Token::Else => write!(f, "else"),
             Token::Fun => write!(f, "fun"),
             Token::For => write!(f, "for"),
             Token::If => write!(f, "if"),
+            Token::Import => write!(f, "import"),
             Token::Print => write!(f, "print"),
             Token::Return => write!(f, "return"),
             Token::Super => write!(f, "super"),
+            Token::Switch => write!(f, "switch"),
             Token::This => write!(f, "this"),
             Token::Var => write!(f, "var"),
             Token::While => write!(f, "while"),
@@ -126,12 +168,15 @@ fn whitespace<'a>() -> Parser<'a, char, ()> {
 }
 
 fn one_char<'a>() -> Parser<'a, char, Token> {
-    one_of("(){},.;+-/*=!<>").map(|ch| match ch {
+    one_of("(){}[],:.;+-/*=!<>&|^").map(|ch| match ch {
         '(' => Token::LeftParen,
         ')' => Token::RightParen,
         '{' => Token::LeftBrace,
         '}' => Token::RightBrace,
+        '[' => Token::LeftBracket,
+        ']' => Token::RightBracket,
         ',' => Token::Comma,
+        ':' => Token::Colon,
         ';' => Token::Semicolon,
         '.' => Token::Dot,
         '+' => Token::O(Operator::Plus),
@@ -142,6 +187,9 @@ fn one_char<'a>() -> Parser<'a, char, Token> {
         '=' => Token::Assign,
         '<' => Token::O(Operator::LessThan),
         '>' => Token::O(Operator::GreaterThan),
+        '&' => Token::O(Operator::BitAnd),
+        '|' => Token::O(Operator::BitOr),
+        '^' => Token::O(Operator::BitXor),
         _ => Token::Illegal(ch.to_string()),
     })
 }
@@ -153,39 +201,68 @@ fn two_char<'a>() -> Parser<'a, char, Token> {
         static ref leq: Vec<char> = "<=".chars().collect();
         static ref geq: Vec<char> = ">=".chars().collect();
         static ref comment: Vec<char> = "//".chars().collect();
+        static ref shl: Vec<char> = "<<".chars().collect();
+        static ref shr: Vec<char> = ">>".chars().collect();
+        static ref plus_eq: Vec<char> = "+=".chars().collect();
+        static ref minus_eq: Vec<char> = "-=".chars().collect();
+        static ref star_eq: Vec<char> = "*=".chars().collect();
+        static ref slash_eq: Vec<char> = "/=".chars().collect();
+        static ref incr: Vec<char> = "++".chars().collect();
+        static ref decr: Vec<char> = "--".chars().collect();
+        static ref pow: Vec<char> = "**".chars().collect();
+        static ref arrow: Vec<char> = "=>".chars().collect();
+        static ref coalesce: Vec<char> = "??".chars().collect();
     }
     seq(&eqeq).map(|_| Token::O(Operator::Equal))
         | seq(&neq).map(|_| Token::O(Operator::NotEqual))
         | seq(&leq).map(|_| Token::O(Operator::LessThanOrEqual))
         | seq(&geq).map(|_| Token::O(Operator::GreaterThanOrEqual))
         | seq(&comment).map(|_| Token::Comment)
+        | seq(&shl).map(|_| Token::O(Operator::ShiftLeft))
+        | seq(&shr).map(|_| Token::O(Operator::ShiftRight))
+        | seq(&plus_eq).map(|_| Token::CompoundAssign(Operator::Plus))
+        | seq(&minus_eq).map(|_| Token::CompoundAssign(Operator::Minus))
+        | seq(&star_eq).map(|_| Token::CompoundAssign(Operator::Star))
+        | seq(&slash_eq).map(|_| Token::CompoundAssign(Operator::Slash))
+        | seq(&incr).map(|_| Token::Increment)
+        | seq(&decr).map(|_| Token::Decrement)
+        | seq(&pow).map(|_| Token::O(Operator::Power))
+        | seq(&arrow).map(|_| Token::Arrow)
+        | seq(&coalesce).map(|_| Token::O(Operator::Coalesce))
 }
 
 fn alpha_num_literal<'a>() -> Parser<'a, char, Token> {
-    -is_a(|ch: char| ch.is_alphabetic()) *
-    is_a(|ch: char| ch.is_alphanumeric()).repeat(1..).map(|lit| {
-        let lit_str: String = lit.into_iter().collect();
-        match lit_str.as_str() {
-            "and" => Token::O(Operator::And),
-            "class" => Token::Class,
-            "else" => Token::Else,
-            "false" => Token::Bool(false),
-            "true" => Token::Bool(true),
-            "fun" => Token::Fun,
-            "for" => Token::For,
-            "if" => Token::If,
-            "nil" => Token::Nil(()),
-            "or" => Token::O(Operator::Or),
-            "print" => Token::Print,
-            "return" => Token::Return,
-            "super" => Token::Super,
-            "this" => Token::This,
-            "var" => Token::Var,
-            "while" => Token::While,
-            lit_str if true => Token::Identifier(lit_str.parse().unwrap()),
-            _ => Token::Illegal(lit_str),
-        }
-    })
+    -is_a(|ch: char| ch.is_alphabetic())
+        * is_a(|ch: char| ch.is_alphanumeric())
+            .repeat(1..)
+            .map(|lit| {
+                let lit_str: String = lit.into_iter().collect();
+                match lit_str.as_str() {
+                    "and" => Token::O(Operator::And),
+                    "case" => Token::Case,
+                    "class" => Token::Class,
+                    "const" => Token::Const,
+                    "default" => Token::Default,
+                    "else" => Token::Else,
+                    "false" => Token::Bool(false),
+                    "true" => Token::Bool(true),
+                    "fun" => Token::Fun,
+                    "for" => Token::For,
+                    "if" => Token::If,
+                    "import" => Token::Import,
+                    "nil" => Token::Nil(()),
+                    "or" => Token::O(Operator::Or),
+                    "print" => Token::Print,
+                    "return" => Token::Return,
+                    "super" => Token::Super,
+                    "switch" => Token::Switch,
+                    "this" => Token::This,
+                    "var" => Token::Var,
+                    "while" => Token::While,
+                    lit_str if true => Token::Identifier(Symbol::intern(lit_str)),
+                    _ => Token::Illegal(lit_str),
+                }
+            })
 }
 
 fn int_literal<'a>() -> Parser<'a, char, Token> {
@@ -194,6 +271,20 @@ fn int_literal<'a>() -> Parser<'a, char, Token> {
         .map(|lit| Token::Float(lit.into_iter().collect::<String>().parse::<f64>().unwrap()))
 }
 
+// Digits are consumed greedily so that `0xG` or a bare `0x` lexes as one illegal token rather
+// than a number followed by an identifier.
+fn radix_literal<'a>(prefix: char, radix: u32) -> Parser<'a, char, Token> {
+    (sym('0') * sym(prefix) * is_a(|ch: char| ch.is_alphanumeric()).repeat(0..)).map(
+        move |digits| {
+            let digits: String = digits.into_iter().collect();
+            match u64::from_str_radix(&digits, radix) {
+                Ok(i) if !digits.starts_with(['+', '-']) => Token::Int(i),
+                _ => Token::Illegal(format!("0{}{}", prefix, digits)),
+            }
+        },
+    )
+}
+
 fn float_literal<'a>() -> Parser<'a, char, Token> {
     (is_a(|ch: char| ch.is_numeric()).repeat(1..)
         + sym('.')
@@ -205,16 +296,205 @@ fn float_literal<'a>() -> Parser<'a, char, Token> {
     })
 }
 
+// A bad escape doesn't end the string, so the whole literal still lexes as one (illegal) token.
+// `\u` takes one to six hex digits in braces; bare digits after it are consumed anyway so that
+// the error can point out the missing braces.
+fn escape<'a>() -> Parser<'a, char, Result<char, String>> {
+    let braced = sym('{') * none_of("}\"").repeat(0..) - sym('}');
+    let bare = is_a(|ch: char| ch.is_ascii_hexdigit()).repeat(0..);
+    let unicode = sym('u') * (braced.map(Ok) | bare.map(Err));
+    sym('\\')
+        * (unicode.map(|digits| match digits {
+            Ok(digits) => {
+                let digits: String = digits.into_iter().collect();
+                Some(&digits)
+                    .filter(|d| {
+                        (1..=6).contains(&d.len()) && d.chars().all(|c| c.is_ascii_hexdigit())
+                    })
+                    .and_then(|d| u32::from_str_radix(d, 16).ok())
+                    .and_then(std::char::from_u32)
+                    .ok_or(format!("\\u{{{}}}", digits))
+            }
+            Err(digits) if digits.is_empty() => Err(r"\u (expected \u{...})".to_string()),
+            Err(digits) => {
+                let digits: String = digits.into_iter().collect();
+                Err(format!(r"\u{} (expected \u{{{}}})", digits, digits))
+            }
+        }) | any().map(|ch| match ch {
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            't' => Ok('\t'),
+            '0' => Ok('\0'),
+            '\\' | '"' => Ok(ch),
+            ch => Err(format!("\\{}", ch)),
+        }))
+}
+
 fn string<'a>() -> Parser<'a, char, Token> {
-    (sym('"') * none_of("\"").repeat(0..) - sym('"'))
-        .map(|s| Token::String(s.into_iter().collect()))
+    (sym('"') * (none_of("\\\"").map(Ok) | escape()).repeat(0..) - sym('"')).map(
+        |chars| match chars.into_iter().collect::<Result<String, String>>() {
+            Ok(s) => Token::String(s),
+            Err(escape) => Token::Illegal(escape),
+        },
+    )
+}
+
+// The inverse of `string()`: quotes `s` so it lexes back to the same string. Control characters
+// without a named escape are written as `\u{..}`.
+pub fn lox_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for ch in s.chars() {
+        match ch {
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' | '"' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            ch if ch.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn token<'a>() -> Parser<'a, char, Token> {
+    alpha_num_literal()
+        | radix_literal('x', 16)
+        | radix_literal('b', 2)
+        | float_literal()
+        | int_literal()
+        | two_char()
+        | one_char()
+        | string()
+        | illegal()
+}
+
+// Anything no other rule accepts becomes a one-character illegal token, so lexing carries on
+// past it and the parser reports it where it appears.
+fn illegal<'a>() -> Parser<'a, char, Token> {
+    any().map(|ch: char| Token::Illegal(ch.to_string()))
 }
 
 pub fn lexer<'a>() -> Parser<'a, char, Vec<Token>> {
-    (whitespace().opt()
-        * (alpha_num_literal() | float_literal() | int_literal() | two_char() | one_char() | string())
-        - whitespace().opt())
-    .repeat(0..)
+    (whitespace().opt() * token() - whitespace().opt())
+        .repeat(0..)
+        .map(|mut tokens| {
+            tokens.push(Token::Eof);
+            tokens
+        })
+}
+
+// Same as `lexer` but pairs each token with the offset of its first character.
+pub fn spanned_lexer<'a>() -> Parser<'a, char, Vec<(Token, usize)>> {
+    ((whitespace().opt() * (empty().pos() + token()) - whitespace().opt()).repeat(0..)
+        + empty().pos())
+    .map(|(tokens, end)| {
+        let mut tokens: Vec<(Token, usize)> = tokens
+            .into_iter()
+            .map(|(offset, token)| (token, offset))
+            .collect();
+        tokens.push((Token::Eof, end));
+        tokens
+    })
+}
+
+pub type SpannedToken = (Token, usize);
+
+// A line and column, both counted from 1.
+pub type Position = (usize, usize);
+
+// Lexes one token per `next`, so nothing past the last token handed out has been scanned. Like
+// `spanned_lexer` it ends with Eof, but each illegal token comes out as an error instead.
+pub struct TokenStream<'a> {
+    input: &'a [char],
+    position: usize,
+    skip: Parser<'a, char, usize>,
+    token: Parser<'a, char, Token>,
+    done: bool,
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn new(input: &'a [char]) -> Self {
+        TokenStream {
+            input,
+            position: 0,
+            skip: whitespace().opt() * empty().pos(),
+            token: token(),
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for TokenStream<'a> {
+    type Item = Result<SpannedToken, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let (start, _) = self.skip.parse_at(self.input, self.position).ok()?;
+        if start == self.input.len() {
+            self.done = true;
+            return Some(Ok((Token::Eof, start)));
+        }
+        let (token, end) = self.token.parse_at(self.input, start).ok()?;
+        self.position = end;
+        match token {
+            Token::Illegal(lit) => Some(Err(LexError::new(&format!(
+                "Unexpected '{}' at offset {}.",
+                lit, start
+            )))),
+            token => Some(Ok((token, start))),
+        }
+    }
+}
+
+pub fn lex_with_lines(input: &[char]) -> pom::Result<(Vec<Token>, Vec<usize>)> {
+    let (tokens, positions) = lex_with_positions(input)?;
+    Ok((
+        tokens,
+        positions.into_iter().map(|(line, _)| line).collect(),
+    ))
+}
+
+// pom ties a built parser to the lifetime of its input, so the combinator graph can't be kept
+// around between calls without unsafe code and is rebuilt for each input instead.
+pub fn lex_spanned(input: &[char]) -> pom::Result<Vec<SpannedToken>> {
+    spanned_lexer().parse(input)
+}
+
+pub fn lex(input: &[char]) -> pom::Result<Vec<Token>> {
+    Ok(lex_spanned(input)?
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect())
+}
+
+// Where each token starts. Columns count chars, not bytes.
+pub fn lex_with_positions(input: &[char]) -> pom::Result<(Vec<Token>, Vec<Position>)> {
+    let (tokens, offsets): (Vec<Token>, Vec<usize>) = lex_spanned(input)?.into_iter().unzip();
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut seen = 0;
+    let positions = offsets
+        .into_iter()
+        .map(|offset| {
+            for (i, ch) in input[seen..offset].iter().enumerate() {
+                if *ch == '\n' {
+                    line += 1;
+                    line_start = seen + i + 1;
+                }
+            }
+            seen = offset;
+            (line, offset - line_start + 1)
+        })
+        .collect();
+    Ok((tokens, positions))
 }
 
 // impl std::convert::From<String> for Token {
@@ -226,14 +506,15 @@ pub fn lexer<'a>() -> Parser<'a, char, Vec<Token>> {
 //
 #[cfg(test)]
 mod tests {
-    use super::lexer;
     use super::Token;
+    use super::{lex, lex_with_lines, lex_with_positions, lexer, spanned_lexer, TokenStream};
+    use crate::intern::Symbol;
     use crate::lexer::Operator;
 
     #[test]
     fn lex_single_char() {
         //a Vec<char> is the owned form of a &[char]
-        let input: Vec<char> = "(} } . ; , + - /* ! = <>".chars().collect();
+        let input: Vec<char> = "(} } [ ] . ; , : + - /* ! = <>".chars().collect();
         let tokens = lexer().parse(&input);
         println!("{:?}", tokens);
         assert_eq!(
@@ -242,9 +523,12 @@ mod tests {
                 Token::LeftParen,
                 Token::RightBrace,
                 Token::RightBrace,
+                Token::LeftBracket,
+                Token::RightBracket,
                 Token::Dot,
                 Token::Semicolon,
                 Token::Comma,
+                Token::Colon,
                 Token::O(Operator::Plus),
                 Token::O(Operator::Minus),
                 Token::O(Operator::Slash),
@@ -253,6 +537,7 @@ mod tests {
                 Token::Assign,
                 Token::O(Operator::LessThan),
                 Token::O(Operator::GreaterThan),
+                Token::Eof,
             ]
         );
     }
@@ -260,7 +545,9 @@ mod tests {
     #[test]
     fn lex_double_char() {
         //a Vec<char> is the owned form of a &[char]
-        let input: Vec<char> = "== != <= >= //".chars().collect();
+        let input: Vec<char> = "== != <= >= += -= *= /= ++ -- ** => ?? //"
+            .chars()
+            .collect();
         let tokens = lexer().parse(&input);
         println!("{:?}", tokens);
         assert_eq!(
@@ -269,7 +556,37 @@ mod tests {
                 Token::O(Operator::NotEqual),
                 Token::O(Operator::LessThanOrEqual),
                 Token::O(Operator::GreaterThanOrEqual),
+                Token::CompoundAssign(Operator::Plus),
+                Token::CompoundAssign(Operator::Minus),
+                Token::CompoundAssign(Operator::Star),
+                Token::CompoundAssign(Operator::Slash),
+                Token::Increment,
+                Token::Decrement,
+                Token::O(Operator::Power),
+                Token::Arrow,
+                Token::O(Operator::Coalesce),
                 Token::Comment,
+                Token::Eof,
+            ],
+            tokens.unwrap()
+        );
+    }
+
+    #[test]
+    fn lex_bitwise() {
+        let input: Vec<char> = "& | ^ << >> < <= >".chars().collect();
+        let tokens = lexer().parse(&input);
+        assert_eq!(
+            vec![
+                Token::O(Operator::BitAnd),
+                Token::O(Operator::BitOr),
+                Token::O(Operator::BitXor),
+                Token::O(Operator::ShiftLeft),
+                Token::O(Operator::ShiftRight),
+                Token::O(Operator::LessThan),
+                Token::O(Operator::LessThanOrEqual),
+                Token::O(Operator::GreaterThan),
+                Token::Eof,
             ],
             tokens.unwrap()
         );
@@ -283,17 +600,29 @@ mod tests {
         println!("{:?}", tokens);
         assert_eq!(
             vec![
-                Token::Identifier(String::from("a")),
-                Token::Identifier(String::from("b")),
-                Token::Identifier(String::from("c")),
+                Token::Identifier(Symbol::intern("a")),
+                Token::Identifier(Symbol::intern("b")),
+                Token::Identifier(Symbol::intern("c")),
                 Token::O(Operator::And),
                 Token::If,
                 Token::Else,
+                Token::Eof,
             ],
             tokens.unwrap()
         );
     }
 
+    #[test]
+    fn lex_keyword_prefixes() {
+        for name in &["andy", "orange", "ifx", "forall", "classy", "superb"] {
+            let input: Vec<char> = name.chars().collect();
+            assert_eq!(
+                lexer().parse(&input).unwrap(),
+                vec![Token::Identifier(Symbol::intern(name)), Token::Eof]
+            );
+        }
+    }
+
     #[test]
     fn lex_string() {
         let input: Vec<char> = "\" a \"\"a\"".chars().collect();
@@ -303,17 +632,178 @@ mod tests {
             vec![
                 Token::String(String::from(" a ")),
                 Token::String(String::from("a")),
+                Token::Eof,
             ],
             tokens.unwrap()
         );
     }
 
+    #[test]
+    fn lex_escapes() {
+        for (source, expected) in &[
+            (
+                r#""a\tb\n\"q\" \\""#,
+                Token::String("a\tb\n\"q\" \\".to_string()),
+            ),
+            (r#""caf\u{E9}""#, Token::String("café".to_string())),
+            (r#""\u{1F600}!""#, Token::String("\u{1F600}!".to_string())),
+            (r#""\u{ZZ}""#, Token::Illegal(r"\u{ZZ}".to_string())),
+            (r#""\u{110000}""#, Token::Illegal(r"\u{110000}".to_string())),
+            (r#""\u{}""#, Token::Illegal(r"\u{}".to_string())),
+            (
+                r#""\u41""#,
+                Token::Illegal(r"\u41 (expected \u{41})".to_string()),
+            ),
+            (
+                r#""\u""#,
+                Token::Illegal(r"\u (expected \u{...})".to_string()),
+            ),
+            (r#""\u{+41}""#, Token::Illegal(r"\u{+41}".to_string())),
+            (
+                r#""\u{0000041}""#,
+                Token::Illegal(r"\u{0000041}".to_string()),
+            ),
+            (r#""\u{10FFFF}""#, Token::String("\u{10FFFF}".to_string())),
+            (r#""\q""#, Token::Illegal(r"\q".to_string())),
+        ] {
+            let input: Vec<char> = source.chars().collect();
+            assert_eq!(
+                lexer().parse(&input).unwrap(),
+                vec![expected.clone(), Token::Eof],
+                "{}",
+                source
+            );
+        }
+    }
+
     #[test]
     fn lex_float() {
         let input: Vec<char> = "3.33".chars().collect();
         let tokens = lexer().parse(&input);
         println!("{:?}", tokens);
-        assert_eq!(tokens.unwrap(), vec![Token::Float(3.33)]);
+        assert_eq!(tokens.unwrap(), vec![Token::Float(3.33), Token::Eof]);
+    }
+
+    #[test]
+    fn lex_radix() {
+        let input: Vec<char> = "0xFF 0b1010 0x 0xG 0b2 0 0.5".chars().collect();
+        assert_eq!(
+            lexer().parse(&input).unwrap(),
+            vec![
+                Token::Int(255),
+                Token::Int(10),
+                Token::Illegal("0x".to_string()),
+                Token::Illegal("0xG".to_string()),
+                Token::Illegal("0b2".to_string()),
+                Token::Float(0.0),
+                Token::Float(0.5),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_radix_bounds() {
+        let input: Vec<char> =
+            "0x7FFFFFFF 0x80000000 0xFFFFFFFF 0xFFFFFFFFFFFFFFFF 0x10000000000000000"
+                .chars()
+                .collect();
+        assert_eq!(
+            lexer().parse(&input).unwrap(),
+            vec![
+                Token::Int(0x7FFF_FFFF),
+                Token::Int(0x8000_0000),
+                Token::Int(0xFFFF_FFFF),
+                Token::Int(u64::MAX),
+                Token::Illegal("0x10000000000000000".to_string()),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_lines() {
+        let input: Vec<char> = "var a\n\n  = 1;\nprint a;\n".chars().collect();
+        let (tokens, lines) = lex_with_lines(&input).unwrap();
+        assert_eq!(tokens, lexer().parse(&input).unwrap());
+        assert_eq!(lines, vec![1, 1, 3, 3, 3, 4, 4, 4, 5]);
+    }
+
+    #[test]
+    fn lex_positions() {
+        let input: Vec<char> = "var a\n\n  = 1;\nprint a;\n".chars().collect();
+        let (_, positions) = lex_with_positions(&input).unwrap();
+        assert_eq!(
+            positions,
+            vec![
+                (1, 1),
+                (1, 5),
+                (3, 3),
+                (3, 5),
+                (3, 6),
+                (4, 1),
+                (4, 7),
+                (4, 8),
+                (5, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_stream() {
+        let input: Vec<char> =
+            "fun f(x) {\n  return x ** 2 >= 0x10 ?? \"a\\tb\";\n}\nprint f(3.5);\n"
+                .chars()
+                .collect();
+        let streamed = TokenStream::new(&input)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(streamed, spanned_lexer().parse(&input).unwrap());
+        assert_eq!(
+            streamed.into_iter().map(|(t, _)| t).collect::<Vec<_>>(),
+            lexer().parse(&input).unwrap()
+        );
+
+        let input: Vec<char> = "var a = 1; @ 0xZ".chars().collect();
+        let mut stream = TokenStream::new(&input).skip(5);
+        assert_eq!(
+            stream.next().unwrap().unwrap_err().message,
+            "Unexpected '@' at offset 11."
+        );
+        assert_eq!(
+            stream.next().unwrap().unwrap_err().message,
+            "Unexpected '0xZ' at offset 13."
+        );
+        assert_eq!(stream.next().unwrap().unwrap(), (Token::Eof, 16));
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn lex_illegal() {
+        let input: Vec<char> = "var @ = 1; #".chars().collect();
+        assert_eq!(
+            spanned_lexer().parse(&input).unwrap(),
+            vec![
+                (Token::Var, 0),
+                (Token::Illegal("@".to_string()), 4),
+                (Token::Assign, 6),
+                (Token::Float(1.0), 8),
+                (Token::Semicolon, 9),
+                (Token::Illegal("#".to_string()), 11),
+                (Token::Eof, 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_eof() {
+        let input: Vec<char> = "".chars().collect();
+        assert_eq!(lexer().parse(&input).unwrap(), vec![Token::Eof]);
+
+        let input: Vec<char> = "var a = 1;".chars().collect();
+        let tokens = lexer().parse(&input).unwrap();
+        assert_eq!(tokens.last(), Some(&Token::Eof));
+        assert_eq!(tokens.iter().filter(|t| **t == Token::Eof).count(), 1);
     }
 
     #[test]
@@ -355,4 +845,16 @@ mod tests {
         println!("{}", Token::O(Operator::Minus));
         println!("{}", Token::RightBrace);
     }
+
+    #[test]
+    fn lex_many() {
+        let snippets: Vec<Vec<char>> = (0..10_000)
+            .map(|i| format!("var a{} = {} + 1;", i, i).chars().collect())
+            .collect();
+        let count: usize = snippets
+            .iter()
+            .map(|snippet| lex(snippet).unwrap().len())
+            .sum();
+        assert_eq!(count, 10_000 * 8);
+    }
 }
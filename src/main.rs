@@ -1,64 +1,556 @@
 #[macro_use]
 extern crate lazy_static;
-#[macro_use]
-extern crate downcast;
 
 #[allow(dead_code)]
-mod symbol_table;
+mod intern;
 #[allow(dead_code)]
 mod interpreter;
 #[allow(dead_code)]
 mod lexer;
 #[allow(dead_code)]
+mod optimizer;
+#[allow(dead_code)]
 mod parser;
+#[allow(dead_code)]
+mod reporter;
+#[allow(dead_code)]
+mod stats;
+#[allow(dead_code)]
+mod symbol_table;
+#[allow(dead_code)]
+mod sync;
+#[allow(dead_code)]
+mod vm;
 
+use crate::interpreter::{stringify, Interpreter, STACK_SIZE};
+use crate::lexer::lex_with_positions;
+use crate::parser::ast::Literal;
+use crate::parser::parser::Parser;
+use crate::parser::printer::AstPrinter;
+use crate::reporter::ErrorReporter;
+use crate::symbol_table::Object;
 use std::env;
 use std::error::Error;
 use std::fs;
 use std::io;
 use std::io::{BufRead, Write};
+use std::panic;
 use std::path::Path;
 use std::process;
+use std::thread;
+use std::time::Instant;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Mode {
+    Run,
+    DumpTokens,
+    DumpAst,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    mode: Mode,
+    script: Option<String>,
+    session: Option<String>,
+    time: bool,
+    prompt: Option<String>,
+}
+
+const USAGE: &str = "Usage: rlox [--dump-tokens | --dump-ast] [--time] [--session file.json] [--prompt text] [script]";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
-        println!("Usage: rlox [script]");
-        process::exit(64);
-    } else if args.len() == 2 {
-        run_file(&args[1]);
-    } else {
-        run_prompt();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Some(options) => Options {
+            prompt: options.prompt.or_else(|| env::var("RLOX_PROMPT").ok()),
+            ..options
+        },
+        None => {
+            println!("{}", USAGE);
+            process::exit(64);
+        }
+    };
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            let result = match &options.script {
+                Some(filepath) => run_file(filepath, &options),
+                None => run_prompt(&options),
+            };
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                process::exit(74);
+            }
+        })
+        .unwrap();
+    if interpreter.join().is_err() {
+        process::exit(70);
     }
 }
 
-fn run_file(filepath: &str) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(Path::new(&String::from(filepath)).as_os_str())?;
-    run(&contents);
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        mode: Mode::Run,
+        script: None,
+        session: None,
+        time: false,
+        prompt: None,
+    };
+    let mut rest = args.iter().peekable();
+    while let Some(flag) = rest.peek().filter(|arg| arg.starts_with("--")) {
+        match flag.as_str() {
+            "--dump-tokens" => options.mode = Mode::DumpTokens,
+            "--dump-ast" => options.mode = Mode::DumpAst,
+            "--time" => options.time = true,
+            "--session" => {
+                rest.next();
+                options.session = Some(rest.peek()?.to_string());
+            }
+            "--prompt" => {
+                rest.next();
+                options.prompt = Some(rest.peek()?.to_string());
+            }
+            _ => return None,
+        };
+        rest.next();
+    }
+    options.script = rest.next().cloned();
+    if rest.next().is_some() {
+        return None;
+    }
+    Some(options)
+}
+
+fn load_session(interpreter: &mut Interpreter, options: &Options) -> Result<(), Box<dyn Error>> {
+    if let Some(session) = &options.session {
+        if Path::new(session).exists() {
+            interpreter.load_globals(&fs::read_to_string(session)?)?;
+        }
+    }
     Ok(())
 }
 
+fn save_session(interpreter: &Interpreter, options: &Options) -> Result<(), Box<dyn Error>> {
+    if let Some(session) = &options.session {
+        fs::write(session, interpreter.dump_globals())?;
+    }
+    Ok(())
+}
+
+fn run_file(filepath: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(Path::new(&String::from(filepath)).as_os_str())?;
+    let mut interpreter = Interpreter::new().with_script(Path::new(filepath));
+    load_session(&mut interpreter, options)?;
+    timed(options.time, &mut io::stderr(), || {
+        run(
+            &contents,
+            options.mode,
+            &mut interpreter,
+            &mut io::stdout().lock(),
+        )
+    })?;
+    save_session(&interpreter, options)
+}
+
 const PROMPT: &str = ">> ";
+const BANNER: &str = concat!("rlox ", env!("CARGO_PKG_VERSION"));
+#[allow(dead_code)]
 static HAD_ERROR: bool = false;
 
-fn run_prompt() -> Result<(), Box<dyn Error>> {
-    let mut reader = io::BufReader::new(io::stdin());
+fn run_prompt(options: &Options) -> Result<(), Box<dyn Error>> {
+    repl(
+        &mut io::BufReader::new(io::stdin()),
+        &mut io::stdout(),
+        options,
+    )
+}
+
+// Reads until EOF (Ctrl-D), so the loop ends as soon as `read_line` returns nothing.
+fn repl(
+    reader: &mut dyn BufRead,
+    out: &mut dyn Write,
+    options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    let mut interpreter = Interpreter::new();
+    load_session(&mut interpreter, options)?;
+    let prompt = options.prompt.as_deref().unwrap_or(PROMPT);
     let mut line = String::new();
+    writeln!(out, "{}", BANNER)?;
 
     loop {
         line.clear();
-        print!("{}", PROMPT);
-        io::stdout().lock().flush()?;
+        write!(out, "{}", prompt)?;
+        out.flush()?;
 
-        reader.read_line(&mut line)?;
-        run(&line);
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        if line.trim_start().starts_with('.') {
+            if !meta_command(line.trim(), &mut interpreter, out)? {
+                return Ok(());
+            }
+            continue;
+        }
+        match timed(options.time, &mut io::stderr(), || {
+            guarded(&mut interpreter, |interpreter| {
+                run(&line, options.mode, interpreter, out)
+            })
+        }) {
+            Ok(()) => {
+                // Echo the last expression's value, except nil so calls made for their effect
+                // stay quiet.
+                match interpreter.take_last_value() {
+                    None | Some(Object::L(Literal::Nil(_))) => {}
+                    Some(value) => writeln!(out, "{}", stringify(&value))?,
+                }
+                save_session(&interpreter, options)?
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+const HELP: &str = "\
+.help   Show this message
+.exit   Leave the REPL
+.clear  Forget every variable and function defined so far
+.vars   List the global variables";
+
+// REPL commands start with a dot so they can't clash with Lox. Returns false on `.exit`.
+fn meta_command(
+    command: &str,
+    interpreter: &mut Interpreter,
+    out: &mut dyn Write,
+) -> Result<bool, Box<dyn Error>> {
+    match command {
+        ".help" => writeln!(out, "{}", HELP)?,
+        ".exit" => return Ok(false),
+        ".clear" => *interpreter = Interpreter::new(),
+        ".vars" => {
+            for (name, value) in interpreter.globals() {
+                writeln!(out, "{} = {}", name, stringify(&value))?;
+            }
+        }
+        _ => eprintln!("Unknown command '{}'. Try .help.", command),
+    }
+    Ok(true)
+}
+
+fn run(
+    source: &str,
+    mode: Mode,
+    interpreter: &mut Interpreter,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    let input: Vec<char> = source.chars().collect();
+    let (tokens, positions) = lex_with_positions(&input)?;
+    if mode == Mode::DumpTokens {
+        for token in &tokens {
+            writeln!(out, "{:?}", token)?;
+        }
+        return Ok(());
+    }
+    let reporter = ErrorReporter::new(source);
+    let mut parser = Parser::with_positions(tokens, positions);
+    let result = parser.parse();
+    for warning in parser.warnings() {
+        eprintln!("{}", reporter.parse_error(warning));
+    }
+    let stmts = result.map_err(|errors| {
+        errors
+            .iter()
+            .map(|e| reporter.parse_error(e))
+            .collect::<Vec<String>>()
+            .join("\n")
+    })?;
+    if mode == Mode::DumpAst {
+        writeln!(out, "{}", AstPrinter.print(&stmts))?;
+        return Ok(());
     }
+    interpreter
+        .interpret(&stmts)
+        .map_err(|e| reporter.runtime_error(&e))?;
+    Ok(())
 }
 
-fn run(source: &String) {
-    println!("{}", source)
+// Interpreter bugs can still panic, so the REPL catches them to keep going.
+// The default hook is swapped out for the duration so no backtrace is printed.
+fn guarded(
+    interpreter: &mut Interpreter,
+    f: impl FnOnce(&mut Interpreter) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| f(interpreter)));
+    panic::set_hook(hook);
+    result.unwrap_or_else(|payload| {
+        interpreter.recover();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        Err(format!("Error: {}", message).into())
+    })
 }
 
+// Timing goes to `err` so it never mixes with the program's own output.
+fn timed<T>(enabled: bool, err: &mut dyn Write, f: impl FnOnce() -> T) -> T {
+    if !enabled {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    let _ = writeln!(err, "Elapsed: {:.3?}", start.elapsed());
+    result
+}
+
+#[allow(dead_code)]
 fn error(line: usize, error: &str, message: &str) {
     eprintln!("[line {}] Error {}: {}", line, error, message)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+    use crate::{guarded, parse_args, repl, run, timed, Mode, Options, BANNER, HELP};
+    use std::io;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn options(mode: Mode, script: Option<&str>, session: Option<&str>) -> Option<Options> {
+        Some(Options {
+            mode,
+            script: script.map(String::from),
+            session: session.map(String::from),
+            time: false,
+            prompt: None,
+        })
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(
+            parse_args(&args(&["--dump-tokens", "script.lox"])),
+            options(Mode::DumpTokens, Some("script.lox"), None)
+        );
+        assert_eq!(
+            parse_args(&args(&["--dump-ast", "script.lox"])),
+            options(Mode::DumpAst, Some("script.lox"), None)
+        );
+        assert_eq!(
+            parse_args(&args(&["--session", "s.json"])),
+            options(Mode::Run, None, Some("s.json"))
+        );
+        assert_eq!(parse_args(&args(&[])), options(Mode::Run, None, None));
+        assert_eq!(
+            parse_args(&args(&["--time", "--dump-ast", "script.lox"])),
+            Some(Options {
+                time: true,
+                ..options(Mode::DumpAst, Some("script.lox"), None).unwrap()
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["--prompt", "lox> "])),
+            Some(Options {
+                prompt: Some("lox> ".to_string()),
+                ..options(Mode::Run, None, None).unwrap()
+            })
+        );
+        assert_eq!(parse_args(&args(&["--session"])), None);
+        assert_eq!(parse_args(&args(&["--prompt"])), None);
+        assert_eq!(parse_args(&args(&["--bogus", "script.lox"])), None);
+        assert_eq!(parse_args(&args(&["a.lox", "b.lox"])), None);
+    }
+
+    #[test]
+    fn dump_tokens() {
+        let mut out = Vec::new();
+        run(
+            "var a = 1;",
+            Mode::DumpTokens,
+            &mut Interpreter::new(),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Var\nIdentifier(\"a\")\nAssign\nFloat(1.0)\nSemicolon\nEof\n"
+        );
+    }
+
+    #[test]
+    fn dump_ast() {
+        let mut out = Vec::new();
+        run(
+            "for (var i = 0; i < 2; i = i + 1) print i;",
+            Mode::DumpAst,
+            &mut Interpreter::new(),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "(block (var i = 0) (while (< i 2) (block (print i) (; (= i (+ i 1))))))\n"
+        );
+    }
+
+    #[test]
+    fn runtime_error_line() {
+        let error = |source: &str| {
+            let error = run(source, Mode::Run, &mut Interpreter::new(), &mut Vec::new())
+                .unwrap_err()
+                .to_string();
+            error.lines().next().unwrap().to_string()
+        };
+        assert_eq!(
+            error("var a = 1;\n\nprint a + b;"),
+            "[line 3] Undefined variable 'b'."
+        );
+        assert_eq!(
+            error("fun f(x) {\n  return -x;\n}\nf(\"s\");"),
+            "[line 2] Operand must be a number."
+        );
+        assert_eq!(
+            error("var s = \"a\";\ns(1);"),
+            "[line 2] Can only call functions and classes."
+        );
+    }
+
+    #[test]
+    fn parse_error_lines() {
+        let error = run(
+            "print 1;\nvar = 1;\nprint (2;",
+            Mode::Run,
+            &mut Interpreter::new(),
+            &mut Vec::new(),
+        )
+        .unwrap_err()
+        .to_string();
+        let lines: Vec<&str> = error.lines().filter(|l| l.starts_with("[line")).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("[line 2] "));
+        assert!(lines[1].starts_with("[line 3] "));
+    }
+
+    #[test]
+    fn error_caret() {
+        let error = |source: &str| {
+            run(source, Mode::Run, &mut Interpreter::new(), &mut Vec::new())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("print 1;\nvar 2 = 1;"),
+            "[line 2] Expect variable name, found Float(2.0)\n  |\n2 | var 2 = 1;\n  |     ^"
+        );
+        assert_eq!(
+            error("var a = 1;\nprint a + b;"),
+            "[line 2] Undefined variable 'b'.\n  |\n2 | print a + b;\n  |           ^"
+        );
+    }
+
+    #[test]
+    fn time() {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        timed(true, &mut err, || {
+            run("1;", Mode::DumpTokens, &mut Interpreter::new(), &mut out)
+        })
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Float(1.0)\nSemicolon\nEof\n"
+        );
+        assert!(String::from_utf8(err).unwrap().starts_with("Elapsed: "));
+
+        let mut err = Vec::new();
+        timed(false, &mut err, || ());
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn guarded_panic() {
+        let mut interpreter = Interpreter::new();
+        let mut out = Vec::new();
+        let result = guarded(&mut interpreter, |_| panic!("boom"));
+        assert_eq!(result.unwrap_err().to_string(), "Error: boom");
+
+        guarded(&mut interpreter, |i| {
+            run("var a = 1;", Mode::Run, i, &mut out)
+        })
+        .unwrap();
+        assert!(run("a;", Mode::Run, &mut interpreter, &mut out).is_ok());
+    }
+
+    #[test]
+    fn repl_prompt() {
+        let mut out = Vec::new();
+        let options = Options {
+            prompt: Some("lox> ".to_string()),
+            ..options(Mode::DumpTokens, None, None).unwrap()
+        };
+        repl(&mut "1;\n".as_bytes(), &mut out, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}\nlox> Float(1.0)\nSemicolon\nEof\nlox> ", BANNER)
+        );
+    }
+
+    #[test]
+    fn repl_eof() {
+        let options = options(Mode::DumpTokens, None, None).unwrap();
+        let mut out = Vec::new();
+        repl(&mut io::empty(), &mut out, &options).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n>> ", BANNER));
+
+        // A last line without a newline still runs before the loop ends.
+        let mut out = Vec::new();
+        repl(&mut "nil".as_bytes(), &mut out, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}\n>> Nil(())\nEof\n>> ", BANNER)
+        );
+    }
+
+    #[test]
+    fn repl_echo() {
+        let options = Options {
+            prompt: Some(String::new()),
+            ..options(Mode::Run, None, None).unwrap()
+        };
+        let mut out = Vec::new();
+        let input = "1 + 1;\nvar a = \"s\";\na;\nnil;\n";
+        repl(&mut input.as_bytes(), &mut out, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}\n2\ns\n", BANNER)
+        );
+    }
+
+    #[test]
+    fn repl_meta_commands() {
+        let options = Options {
+            prompt: Some(String::new()),
+            ..options(Mode::Run, None, None).unwrap()
+        };
+        let vars = |input: &str| {
+            let mut out = Vec::new();
+            repl(&mut input.as_bytes(), &mut out, &options).unwrap();
+            String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with("a = "))
+                .map(String::from)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(vars("var a = 1;\n.vars\n"), vec!["a = 1"]);
+        assert!(vars("var a = 1;\n.clear\n.vars\n").is_empty());
+        assert!(vars("var a = 1;\n.exit\n.vars\n").is_empty());
+
+        let mut out = Vec::new();
+        repl(&mut ".help\n".as_bytes(), &mut out, &options).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains(HELP));
+    }
+}
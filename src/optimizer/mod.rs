@@ -0,0 +1,408 @@
+use crate::lexer::Operator;
+use crate::parser::ast::{Expr, ExprData, ExprVisitor, Literal, Stmt, StmtData, StmtVisitor};
+
+pub struct Optimizer;
+
+impl Optimizer {
+    pub fn optimize(&mut self, stmts: &[Stmt]) -> Vec<Stmt> {
+        stmts.iter().map(|stmt| stmt.accept(self)).collect()
+    }
+
+    pub fn fold(&mut self, expr: &Expr) -> Expr {
+        expr.accept(self)
+    }
+
+    fn fold_box(&mut self, expr: &Expr) -> Box<Expr> {
+        Box::new(expr.accept(self))
+    }
+}
+
+// Only folds operations that can't fail at runtime, so e.g. `1 / 0` is left for the interpreter
+// to report.
+fn fold_binary(left: &Literal, operator: &Operator, right: &Literal) -> Option<Literal> {
+    match (left, right) {
+        (Literal::Float(left), Literal::Float(right)) => Some(match operator {
+            Operator::Plus => Literal::Float(left + right),
+            Operator::Minus => Literal::Float(left - right),
+            Operator::Star => Literal::Float(left * right),
+            Operator::Power => Literal::Float(left.powf(*right)),
+            Operator::Slash if *right != 0.0 => Literal::Float(left / right),
+            Operator::Equal => Literal::Bool(left == right),
+            Operator::NotEqual => Literal::Bool(left != right),
+            Operator::GreaterThan => Literal::Bool(left > right),
+            Operator::GreaterThanOrEqual => Literal::Bool(left >= right),
+            Operator::LessThan => Literal::Bool(left < right),
+            Operator::LessThanOrEqual => Literal::Bool(left <= right),
+            _ => return None,
+        }),
+        (Literal::String(left), Literal::String(right)) if *operator == Operator::Plus => {
+            Some(Literal::concat(left, right))
+        }
+        _ => None,
+    }
+}
+
+impl ExprVisitor for Optimizer {
+    type Result = Expr;
+
+    fn visit_literal(&mut self, expr: &Literal) -> Self::Result {
+        Expr::L(expr.clone())
+    }
+
+    fn visit_unary(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Unary {
+            operator,
+            right,
+            position,
+        } = expr
+        {
+            let right = self.fold(right);
+            return match (operator, right) {
+                (Operator::Minus, Expr::L(Literal::Float(f))) => Expr::L(Literal::Float(-f)),
+                (Operator::Not, Expr::L(Literal::Bool(b))) => Expr::L(Literal::Bool(!b)),
+                (Operator::Not, Expr::L(Literal::Nil(_))) => Expr::L(Literal::Bool(true)),
+                (operator, right) => Expr::Unary {
+                    operator: operator.clone(),
+                    right: Box::new(right),
+                    position: *position,
+                },
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_binary(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Binary {
+            left,
+            operator,
+            right,
+            position,
+        } = expr
+        {
+            let left = self.fold(left);
+            let right = self.fold(right);
+            if let (Expr::L(l), Expr::L(r)) = (&left, &right) {
+                if let Some(folded) = fold_binary(l, operator, r) {
+                    return Expr::L(folded);
+                }
+            }
+            return Expr::Binary {
+                left: Box::new(left),
+                operator: operator.clone(),
+                right: Box::new(right),
+                position: *position,
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_logical(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Logical {
+            left,
+            operator,
+            right,
+        } = expr
+        {
+            return Expr::Logical {
+                left: self.fold_box(left),
+                operator: operator.clone(),
+                right: self.fold_box(right),
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_grouping(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Grouping { expression } = expr {
+            return match self.fold(expression) {
+                l @ Expr::L(_) => l,
+                expression => Expr::Grouping {
+                    expression: Box::new(expression),
+                },
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_assign(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Assign { name, value } = expr {
+            return Expr::Assign {
+                name: name.clone(),
+                value: self.fold_box(value),
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_postfix(&mut self, expr: &Expr) -> Self::Result {
+        expr.clone()
+    }
+
+    fn visit_variable(&mut self, expr: &Expr) -> Self::Result {
+        expr.clone()
+    }
+
+    fn visit_call(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Call {
+            callee,
+            arguments,
+            position,
+        } = expr
+        {
+            return Expr::Call {
+                callee: self.fold_box(callee),
+                arguments: arguments.iter().map(|a| self.fold(a)).collect(),
+                position: *position,
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_list(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::List { elements } = expr {
+            return Expr::List {
+                elements: elements.iter().map(|e| self.fold(e)).collect(),
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_map(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Map { entries } = expr {
+            return Expr::Map {
+                entries: entries
+                    .iter()
+                    .map(|(k, v)| (self.fold(k), self.fold(v)))
+                    .collect(),
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_index(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Index { object, index } = expr {
+            return Expr::Index {
+                object: self.fold_box(object),
+                index: self.fold_box(index),
+            };
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_set_index(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::SetIndex {
+            object,
+            index,
+            value,
+        } = expr
+        {
+            return Expr::SetIndex {
+                object: self.fold_box(object),
+                index: self.fold_box(index),
+                value: self.fold_box(value),
+            };
+        }
+        panic!("{:?}", expr)
+    }
+}
+
+impl StmtVisitor for Optimizer {
+    type Result = Stmt;
+
+    fn visit_expr_stmt(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Expr(expr) = stmt {
+            return Stmt::Expr(self.fold_box(expr));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_print(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Print(exprs) = stmt {
+            return Stmt::Print(exprs.iter().map(|e| self.fold(e)).collect());
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_var_decl(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Variable { name, initializer } = stmt {
+            return Stmt::Variable {
+                name: name.clone(),
+                initializer: initializer.as_ref().map(|i| self.fold_box(i)),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_var_list(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::VarList(stmts) = stmt {
+            return Stmt::VarList(self.optimize(stmts));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_const(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Const { name, initializer } = stmt {
+            return Stmt::Const {
+                name: name.clone(),
+                initializer: self.fold_box(initializer),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_block(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Block(stmts) = stmt {
+            return Stmt::Block(self.optimize(stmts));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_if(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } = stmt
+        {
+            return Stmt::If {
+                condition: self.fold_box(condition),
+                then_branch: Box::new(then_branch.accept(self)),
+                else_branch: else_branch.as_ref().map(|e| Box::new(e.accept(self))),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_while(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::While { condition, body } = stmt {
+            return Stmt::While {
+                condition: self.fold_box(condition),
+                body: Box::new(body.accept(self)),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_switch(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Switch {
+            subject,
+            cases,
+            default,
+        } = stmt
+        {
+            return Stmt::Switch {
+                subject: self.fold_box(subject),
+                cases: cases
+                    .iter()
+                    .map(|(value, body)| (self.fold(value), self.optimize(body)))
+                    .collect(),
+                default: default.as_ref().map(|body| self.optimize(body)),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_function_decl(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Function {
+            name,
+            parameters,
+            body,
+            ret,
+        } = stmt
+        {
+            return Stmt::Function {
+                name: name.clone(),
+                parameters: parameters.clone(),
+                body: Box::new(body.accept(self)),
+                ret: ret.as_ref().map(|r| self.fold_box(r)),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_return(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Return(value) = stmt {
+            return Stmt::Return(value.as_ref().map(|v| self.fold_box(v)));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_import(&mut self, stmt: &Stmt) -> Self::Result {
+        stmt.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intern::Symbol;
+    use crate::lexer::{lex, Operator, Token};
+    use crate::optimizer::Optimizer;
+    use crate::parser::ast::{Expr, Literal, Stmt};
+    use crate::parser::parser::Parser;
+
+    fn optimize(source: &str) -> Vec<Stmt> {
+        let input: Vec<char> = source.chars().collect();
+        let tokens = lex(&input).unwrap();
+        Optimizer.optimize(&Parser::new(tokens).parse().unwrap())
+    }
+
+    #[test]
+    fn fold_constants() {
+        assert_eq!(
+            optimize("print (2 + 3) * 4 - -1;"),
+            vec![Stmt::Print(vec![Expr::L(Literal::Float(21.0))])]
+        );
+        assert_eq!(
+            optimize(r#"var s = "a" + "b"; var b = !(1 < 2);"#),
+            vec![
+                Stmt::Variable {
+                    name: Token::Identifier(Symbol::intern("s")),
+                    initializer: Some(Box::new(Expr::L(Literal::String("ab".to_string().into())))),
+                },
+                Stmt::Variable {
+                    name: Token::Identifier(Symbol::intern("b")),
+                    initializer: Some(Box::new(Expr::L(Literal::Bool(false)))),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fold_nested_unary() {
+        for source in &["print - -5;", "print -(-5);"] {
+            assert_eq!(
+                optimize(source),
+                vec![Stmt::Print(vec![Expr::L(Literal::Float(5.0))])]
+            );
+        }
+    }
+
+    #[test]
+    fn fold_partially() {
+        assert_eq!(
+            optimize("x + 2 * 3;"),
+            vec![Stmt::Expr(Box::new(Expr::Binary {
+                left: Box::new(Expr::Variable {
+                    name: Token::Identifier(Symbol::intern("x")),
+                    position: None,
+                }),
+                operator: Operator::Plus,
+                right: Box::new(Expr::L(Literal::Float(6.0))),
+                position: None,
+            }))]
+        );
+    }
+
+    #[test]
+    fn skip_division_by_zero() {
+        assert_eq!(
+            optimize("1 / (1 - 1);"),
+            vec![Stmt::Expr(Box::new(Expr::Binary {
+                left: Box::new(Expr::L(Literal::Float(1.0))),
+                operator: Operator::Slash,
+                right: Box::new(Expr::L(Literal::Float(0.0))),
+                position: None,
+            }))]
+        );
+    }
+}
@@ -1,25 +1,38 @@
 use crate::lexer;
+use crate::sync::Rc;
+use std::fmt;
 
 pub trait StmtData {
-    fn accept<V: StmtVisitor>(&self, visitor: &mut V);
+    fn accept<V: StmtVisitor>(&self, visitor: &mut V) -> V::Result;
 }
 
 pub trait StmtVisitor {
-    fn visit_expr_stmt(&mut self, stmt: &Stmt);
-    fn visit_print(&mut self, stmt: &Stmt);
-    fn visit_var_decl(&mut self, stmt: &Stmt);
-    fn visit_block(&mut self, stmt: &Stmt);
-    fn visit_if(&mut self, stmt: &Stmt);
-    fn visit_while(&mut self, stmt: &Stmt);
-    fn visit_function_decl(&mut self, stmt: &Stmt);
-    fn visit_return(&mut self, stmt: &Stmt);
+    type Result;
+
+    fn visit_expr_stmt(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_print(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_var_decl(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_var_list(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_const(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_block(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_if(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_while(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_switch(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_function_decl(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_return(&mut self, stmt: &Stmt) -> Self::Result;
+    fn visit_import(&mut self, stmt: &Stmt) -> Self::Result;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Expr(Box<Expr>),
-    Print(Box<Expr>),
+    Print(Vec<Expr>),
     Variable {
+        name: lexer::Token,
+        initializer: Option<Box<Expr>>,
+    },
+    VarList(Vec<Stmt>),
+    Const {
         name: lexer::Token,
         initializer: Box<Expr>,
     },
@@ -33,6 +46,11 @@ pub enum Stmt {
         condition: Box<Expr>,
         body: Box<Stmt>,
     },
+    Switch {
+        subject: Box<Expr>,
+        cases: Vec<(Expr, Vec<Stmt>)>,
+        default: Option<Vec<Stmt>>,
+    },
     Function {
         name: lexer::Token,
         parameters: Option<Vec<lexer::Token>>,
@@ -40,31 +58,36 @@ pub enum Stmt {
         ret: Option<Box<Expr>>,
     },
     Return(Option<Box<Expr>>),
+    Import(String),
 }
 
 impl StmtData for Stmt {
-    fn accept<V: StmtVisitor>(&self, visitor: &mut V) {
+    fn accept<V: StmtVisitor>(&self, visitor: &mut V) -> V::Result {
         match self {
             s @ Stmt::Expr(_) => visitor.visit_expr_stmt(s),
             s @ Stmt::Print(_) => visitor.visit_print(s),
             s @ Stmt::Variable { .. } => visitor.visit_var_decl(s),
+            s @ Stmt::VarList(_) => visitor.visit_var_list(s),
+            s @ Stmt::Const { .. } => visitor.visit_const(s),
             s @ Stmt::Block(_) => visitor.visit_block(s),
             s @ Stmt::If { .. } => visitor.visit_if(s),
             s @ Stmt::While { .. } => visitor.visit_while(s),
+            s @ Stmt::Switch { .. } => visitor.visit_switch(s),
             s @ Stmt::Function { .. } => visitor.visit_function_decl(s),
             s @ Stmt::Return(_) => visitor.visit_return(s),
+            s @ Stmt::Import(_) => visitor.visit_import(s),
         }
     }
 }
 
 pub trait ExprData {
-    fn accept<V: ExprVisitor>(&self, visitor: &mut V) -> Option<V::Result>;
+    fn accept<V: ExprVisitor>(&self, visitor: &mut V) -> V::Result;
 }
 
 pub trait ExprVisitor {
     type Result;
 
-    fn visit_expr(&mut self, expr: &Expr) -> Option<Self::Result> {
+    fn visit_expr(&mut self, expr: &Expr) -> Self::Result {
         match expr {
             Expr::L(l) => self.visit_literal(l),
             e @ Expr::Unary { .. } => self.visit_unary(e),
@@ -72,43 +95,92 @@ pub trait ExprVisitor {
             e @ Expr::Logical { .. } => self.visit_logical(e),
             e @ Expr::Grouping { .. } => self.visit_grouping(e),
             e @ Expr::Assign { .. } => self.visit_assign(e),
+            e @ Expr::Postfix { .. } => self.visit_postfix(e),
             e @ Expr::Variable { .. } => self.visit_variable(e),
             e @ Expr::Call { .. } => self.visit_call(e),
+            e @ Expr::List { .. } => self.visit_list(e),
+            e @ Expr::Map { .. } => self.visit_map(e),
+            e @ Expr::Index { .. } => self.visit_index(e),
+            e @ Expr::SetIndex { .. } => self.visit_set_index(e),
         }
     }
-    fn visit_literal(&mut self, expr: &Literal) -> Option<Self::Result>;
-    fn visit_unary(&mut self, expr: &Expr) -> Option<Self::Result>;
-    fn visit_binary(&mut self, expr: &Expr) -> Option<Self::Result>;
-    fn visit_logical(&mut self, expr: &Expr) -> Option<Self::Result>;
-    fn visit_grouping(&mut self, expr: &Expr) -> Option<Self::Result>;
-    fn visit_assign(&mut self, expr: &Expr) -> Option<Self::Result>;
-    fn visit_variable(&mut self, expr: &Expr) -> Option<Self::Result>;
-    fn visit_call(&mut self, expr: &Expr) -> Option<Self::Result>;
+    fn visit_literal(&mut self, expr: &Literal) -> Self::Result;
+    fn visit_unary(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_binary(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_logical(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_grouping(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_assign(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_postfix(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_variable(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_call(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_list(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_map(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_index(&mut self, expr: &Expr) -> Self::Result;
+    fn visit_set_index(&mut self, expr: &Expr) -> Self::Result;
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     Float(f64),
     Bool(bool),
-    String(String),
+    String(Rc<String>),
     Nil(()),
 }
 
+// f64's Display is already the shortest form that round-trips, and drops the fraction of whole
+// numbers, so 5.0 prints as 5 like reference Lox. Negative zero keeps its sign and prints -0.
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::Float(n) => write!(f, "{}", n),
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::String(s) => write!(f, "{}", s),
+            Literal::Nil(_) => write!(f, "nil"),
+        }
+    }
+}
+
+// Strings share their buffer, so reading a variable or passing a string to a function doesn't
+// copy it. Concatenation builds a new buffer sized for both halves, unless the left string has no
+// other owner, in which case the right is appended to it in place and the buffer grows as a
+// `String` does, so building a string up piece by piece is linear.
+impl Literal {
+    pub fn concat(left: &str, right: &str) -> Literal {
+        let mut s = String::with_capacity(left.len() + right.len());
+        s.push_str(left);
+        s.push_str(right);
+        Literal::String(s.into())
+    }
+
+    pub fn append(mut left: Rc<String>, right: &str) -> Literal {
+        match Rc::get_mut(&mut left) {
+            Some(s) => {
+                s.push_str(right);
+                Literal::String(left)
+            }
+            None => Literal::concat(&left, right),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
     L(Literal),
     Unary {
         operator: lexer::Operator,
         right: Box<Expr>,
+        position: Option<lexer::Position>,
     },
     Binary {
         left: Box<Expr>,
         operator: lexer::Operator,
         right: Box<Expr>,
+        position: Option<lexer::Position>,
     },
     Call {
         callee: Box<Expr>,
         arguments: Vec<Expr>,
+        position: Option<lexer::Position>,
     },
     Logical {
         left: Box<Expr>,
@@ -122,13 +194,48 @@ pub enum Expr {
         name: lexer::Token,
         value: Box<Expr>,
     },
+    // `x++` or `x--`, with `operator` the step (`+` or `-`) applied to the variable.
+    Postfix {
+        name: lexer::Token,
+        operator: lexer::Operator,
+        position: Option<lexer::Position>,
+    },
     Variable {
         name: lexer::Token,
+        position: Option<lexer::Position>,
+    },
+    List {
+        elements: Vec<Expr>,
+    },
+    Map {
+        entries: Vec<(Expr, Expr)>,
+    },
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
     },
+    SetIndex {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+}
+
+impl Expr {
+    pub fn position(&self) -> Option<lexer::Position> {
+        match self {
+            Expr::Unary { position, .. }
+            | Expr::Binary { position, .. }
+            | Expr::Call { position, .. }
+            | Expr::Postfix { position, .. }
+            | Expr::Variable { position, .. } => *position,
+            _ => None,
+        }
+    }
 }
 
 impl ExprData for Expr {
-    fn accept<V: ExprVisitor>(&self, visitor: &mut V) -> Option<V::Result> {
+    fn accept<V: ExprVisitor>(&self, visitor: &mut V) -> V::Result {
         match self {
             Expr::L(l) => visitor.visit_literal(l),
             e @ Expr::Unary { .. } => visitor.visit_unary(e),
@@ -136,8 +243,13 @@ impl ExprData for Expr {
             e @ Expr::Logical { .. } => visitor.visit_logical(e),
             e @ Expr::Grouping { .. } => visitor.visit_grouping(e),
             e @ Expr::Assign { .. } => visitor.visit_assign(e),
+            e @ Expr::Postfix { .. } => visitor.visit_postfix(e),
             e @ Expr::Variable { .. } => visitor.visit_variable(e),
             e @ Expr::Call { .. } => visitor.visit_call(e),
+            e @ Expr::List { .. } => visitor.visit_list(e),
+            e @ Expr::Map { .. } => visitor.visit_map(e),
+            e @ Expr::Index { .. } => visitor.visit_index(e),
+            e @ Expr::SetIndex { .. } => visitor.visit_set_index(e),
         }
     }
 }
@@ -1,5 +1,8 @@
 pub mod ast;
+pub mod error;
+#[allow(clippy::module_inception)]
 pub mod parser;
+pub mod printer;
 
 #[cfg(test)]
 mod tests {
@@ -16,8 +19,8 @@ mod tests {
         "
         .chars()
         .collect();
-        let tokens = lexer::lexer().parse(&input).unwrap();
+        let tokens = lexer::lex(&input).unwrap();
         let mut p = parser::Parser::new(tokens);
-        Interpreter::new().interpret(&p.parse());
+        Interpreter::new().interpret(&p.parse().unwrap()).unwrap();
     }
 }
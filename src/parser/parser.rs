@@ -2,512 +2,962 @@ use crate::lexer;
 use crate::lexer::{Operator, Token};
 use crate::parser::ast;
 use crate::parser::ast::Stmt;
+use crate::parser::error::ParseError;
 
 pub struct Parser {
     tokens: Vec<lexer::Token>,
+    lines: Vec<usize>,
+    columns: Vec<usize>,
     current: usize,
+    warnings: Vec<ParseError>,
+    // Errors found in a statement that still parsed to the end, so there's nothing to recover from.
+    errors: Vec<ParseError>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<lexer::Token>) -> Parser {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            lines: Vec::new(),
+            columns: Vec::new(),
+            current: 0,
+            warnings: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn with_positions(tokens: Vec<lexer::Token>, positions: Vec<lexer::Position>) -> Parser {
+        let (lines, columns) = positions.into_iter().unzip();
+        Parser {
+            tokens,
+            lines,
+            columns,
+            current: 0,
+            warnings: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<ast::Stmt>, Vec<ParseError>> {
+        match self.parse_partial() {
+            (statements, errors) if errors.is_empty() => Ok(statements),
+            (_, errors) => Err(errors),
+        }
     }
 
-    pub fn parse(&mut self) -> Vec<ast::Stmt> {
+    // Problems that don't stop the program from parsing, such as a missing `;` before a `}`.
+    pub fn warnings(&self) -> &[ParseError] {
+        &self.warnings
+    }
+
+    // A declaration that fails to parse is recorded and skipped, and parsing goes on from the next
+    // statement so that every error is reported at once alongside the statements that did parse.
+    pub fn parse_partial(&mut self) -> (Vec<ast::Stmt>, Vec<ParseError>) {
         let mut statements: Vec<ast::Stmt> = Vec::new();
-        while self.current < self.tokens.len() {
-            statements.push(self.delaration());
+        let mut errors = Vec::new();
+        while !self.is_at_end() {
+            let declaration = self.delaration();
+            errors.append(&mut self.errors);
+            match declaration {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => {
+                    errors.push(error);
+                    self.synchronize();
+                }
+            }
         }
-        statements
+        (statements, errors)
     }
 
-    pub fn statement(&mut self) -> ast::Stmt {
-        if self.tokens.get(self.current).unwrap().clone() == lexer::Token::For {
-            self.current += 1;
+    // Skips past the end of the broken statement: just after a `;`, or up to a keyword that
+    // starts a new one.
+    fn synchronize(&mut self) {
+        while !self.is_at_end() {
+            if *self.advance() == lexer::Token::Semicolon {
+                return;
+            }
+            match self.peek() {
+                lexer::Token::Class
+                | lexer::Token::Fun
+                | lexer::Token::Var
+                | lexer::Token::Const
+                | lexer::Token::For
+                | lexer::Token::If
+                | lexer::Token::While
+                | lexer::Token::Print
+                | lexer::Token::Return
+                | lexer::Token::Switch
+                | lexer::Token::Import => return,
+                _ => {}
+            }
+        }
+    }
+
+    // An error at the token the parser stopped on.
+    fn error(&self, message: &str) -> ParseError {
+        let current = self.current.min(self.tokens.len() - 1);
+        ParseError::new(message)
+            .at(self.lines.get(current).copied())
+            .at_column(self.columns.get(current).copied())
+    }
+
+    pub fn statement(&mut self) -> Result<ast::Stmt, ParseError> {
+        if self.matches(&[lexer::Token::For]) {
             return self.for_stmt();
-        } else if self.tokens.get(self.current).unwrap().clone() == lexer::Token::If {
-            self.current += 1;
+        } else if self.matches(&[lexer::Token::If]) {
             return self.if_stmt();
-        } else if self.tokens.get(self.current).unwrap().clone() == lexer::Token::Print {
-            self.current += 1;
+        } else if self.matches(&[lexer::Token::Print]) {
             return self.print();
-        } else if self.tokens.get(self.current).unwrap().clone() == lexer::Token::Return {
-            self.current += 1;
+        } else if self.matches(&[lexer::Token::Return]) {
             return self.return_stmt();
-        } else if self.tokens.get(self.current).unwrap().clone() == lexer::Token::While {
-            self.current += 1;
+        } else if self.matches(&[lexer::Token::Switch]) {
+            return self.switch_stmt();
+        } else if self.matches(&[lexer::Token::While]) {
             return self.while_stmt();
-        } else if self.tokens.get(self.current).unwrap().clone() == lexer::Token::LeftBrace {
-            self.current += 1;
-            return Stmt::Block(self.block());
+        } else if self.matches(&[lexer::Token::LeftBrace]) {
+            return Ok(Stmt::Block(self.block()?));
         }
         self.expr_stmt()
     }
 
-    pub fn expr_stmt(&mut self) -> ast::Stmt {
-        let expr = self.expression();
-        self.consume(lexer::Token::Semicolon);
-        ast::Stmt::Expr(Box::new(expr))
+    pub fn expr_stmt(&mut self) -> Result<ast::Stmt, ParseError> {
+        let expr = self.expression()?;
+        self.consume_terminator("Expect ';' after expression.")?;
+        Ok(ast::Stmt::Expr(Box::new(expr)))
     }
 
-    pub fn for_stmt(&mut self) -> ast::Stmt {
-        self.consume(Token::LeftParen);
+    pub fn for_stmt(&mut self) -> Result<ast::Stmt, ParseError> {
+        self.consume(Token::LeftParen, "Expect '(' after 'for'.")?;
         let mut initializer = None;
         let mut condition = None;
         let mut increment = None;
-        if let Some(token) = self.tokens.get(self.current) {
-            if token == &lexer::Token::Semicolon {
-                self.current += 1;
-            } else if token == &lexer::Token::Var {
-                self.current += 1;
-                initializer = Some(self.var_decl());
-            } else {
-                initializer = Some(self.expr_stmt());
-            }
+        if self.matches(&[lexer::Token::Var]) {
+            initializer = Some(self.var_decl()?);
+        } else if !self.matches(&[lexer::Token::Semicolon]) {
+            initializer = Some(self.expr_stmt()?);
+        }
 
-            if let Some(token) = self.tokens.get(self.current) {
-                if token != &lexer::Token::Semicolon {
-                    condition = Some(self.expression());
-                }
-            }
-            self.consume(lexer::Token::Semicolon);
+        if !self.check(&lexer::Token::Semicolon) {
+            condition = Some(self.expression()?);
+        }
+        self.consume(lexer::Token::Semicolon, "Expect ';' after loop condition.")?;
 
-            if let Some(token) = self.tokens.get(self.current) {
-                if token != &lexer::Token::RightParen {
-                    increment = Some(self.expression());
-                }
-            }
-            self.consume(lexer::Token::RightParen);
-            let mut body = self.statement();
+        if !self.check(&lexer::Token::RightParen) {
+            increment = Some(self.expression()?);
+        }
+        self.consume(lexer::Token::RightParen, "Expect ')' after for clauses.")?;
+        let mut body = self.statement()?;
 
-            if let Some(increment) = increment {
-                body = Stmt::Block(vec![body, Stmt::Expr(Box::new(increment))])
-            }
+        if let Some(increment) = increment {
+            body = Stmt::Block(vec![body, Stmt::Expr(Box::new(increment))])
+        }
 
-            if let Some(condition) = condition {
-                body = Stmt::While {
-                    condition: Box::new(condition),
-                    body: Box::new(body),
-                }
-            }
+        // A missing condition loops forever, so the while is always emitted.
+        body = Stmt::While {
+            condition: Box::new(condition.unwrap_or(ast::Expr::L(ast::Literal::Bool(true)))),
+            body: Box::new(body),
+        };
 
-            if let Some(initializer) = initializer {
-                body = Stmt::Block(vec![initializer, body])
-            }
-            return body;
-        } else {
-            panic!()
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body])
         }
+        Ok(body)
     }
 
-    pub fn if_stmt(&mut self) -> ast::Stmt {
-        self.consume(Token::LeftParen);
-        let condition = self.expression();
-        self.consume(Token::RightParen);
+    pub fn if_stmt(&mut self) -> Result<ast::Stmt, ParseError> {
+        self.consume(Token::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(Token::RightParen, "Expect ')' after if condition.")?;
 
-        let then_branch = self.statement();
+        // A nested if in the then branch consumes any `else` first, so a dangling else binds to the
+        // nearest if, and `else if` chains need no special casing.
+        let then_branch = self.statement()?;
         let mut else_branch = None;
-        if let Some(token) = self.tokens.get(self.current) {
-            if token == &lexer::Token::Else {
-                self.current += 1;
-                else_branch = Some(Box::new(self.statement()));
-            }
+        if self.matches(&[lexer::Token::Else]) {
+            else_branch = Some(Box::new(self.statement()?));
         }
-        ast::Stmt::If {
+        Ok(ast::Stmt::If {
             condition: Box::new(condition),
             then_branch: Box::new(then_branch),
             else_branch,
-        }
+        })
     }
 
-    pub fn return_stmt(&mut self) -> ast::Stmt {
-        if let Some(token) = self.tokens.get(self.current) {
-            let mut val = ast::Stmt::Return(None);
-            if token != &lexer::Token::Semicolon {
-                val = ast::Stmt::Return(Some(Box::new(self.expression())));
-            }
-            self.consume(lexer::Token::Semicolon);
-            return val;
-        } else {
-            panic!("{:?}", self.tokens.get(self.current))
+    pub fn return_stmt(&mut self) -> Result<ast::Stmt, ParseError> {
+        let mut val = ast::Stmt::Return(None);
+        if !self.check(&lexer::Token::Semicolon) {
+            val = ast::Stmt::Return(Some(Box::new(self.expression()?)));
         }
+        self.consume(lexer::Token::Semicolon, "Expect ';' after return value.")?;
+        Ok(val)
     }
 
-    pub fn while_stmt(&mut self) -> ast::Stmt {
-        self.consume(Token::LeftParen);
-        let condition = self.expression();
-        self.consume(Token::RightParen);
+    pub fn while_stmt(&mut self) -> Result<ast::Stmt, ParseError> {
+        self.consume(Token::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(Token::RightParen, "Expect ')' after condition.")?;
 
-        let body = self.statement();
-        ast::Stmt::While {
+        let body = self.statement()?;
+        Ok(ast::Stmt::While {
             condition: Box::new(condition),
             body: Box::new(body),
-        }
+        })
     }
 
-    pub fn print(&mut self) -> ast::Stmt {
-        let value = self.expression();
-        self.consume(lexer::Token::Semicolon);
-        ast::Stmt::Print(Box::new(value))
+    // Cases don't fall through, so each body runs up to the next `case`, `default` or `}`.
+    pub fn switch_stmt(&mut self) -> Result<ast::Stmt, ParseError> {
+        self.consume(Token::LeftParen, "Expect '(' after 'switch'.")?;
+        let subject = self.expression()?;
+        self.consume(Token::RightParen, "Expect ')' after switch subject.")?;
+        self.consume(Token::LeftBrace, "Expect '{' before switch cases.")?;
+
+        let mut cases = Vec::new();
+        let mut default = None;
+        while !self.is_at_end() && !self.check(&lexer::Token::RightBrace) {
+            if self.matches(&[lexer::Token::Case]) {
+                let value = self.expression()?;
+                self.consume(Token::Colon, "Expect ':' after case value.")?;
+                cases.push((value, self.case_body()?));
+            } else if self.check(&lexer::Token::Default) {
+                if default.is_some() {
+                    let error = self.error("Duplicate 'default' in switch.");
+                    self.errors.push(error);
+                }
+                self.advance();
+                self.consume(Token::Colon, "Expect ':' after 'default'.")?;
+                default = Some(self.case_body()?);
+            } else {
+                return Err(self.error(&format!(
+                    "Expect 'case' or 'default', found {:?}",
+                    self.peek()
+                )));
+            }
+        }
+        self.consume(Token::RightBrace, "Expect '}' after switch cases.")?;
+        Ok(ast::Stmt::Switch {
+            subject: Box::new(subject),
+            cases,
+            default,
+        })
     }
 
-    pub fn block(&mut self) -> Vec<ast::Stmt> {
+    fn case_body(&mut self) -> Result<Vec<ast::Stmt>, ParseError> {
         let mut statements = Vec::new();
-        while self.current < self.tokens.len()
-            && self.tokens.get(self.current).unwrap().clone() != lexer::Token::RightBrace
+        while !self.is_at_end()
+            && !self.check(&lexer::Token::Case)
+            && !self.check(&lexer::Token::Default)
+            && !self.check(&lexer::Token::RightBrace)
         {
-            statements.push(self.delaration());
+            statements.push(self.delaration()?);
         }
-        self.consume(lexer::Token::RightBrace);
-        statements
+        Ok(statements)
     }
 
-    pub fn delaration(&mut self) -> ast::Stmt {
-        if self.tokens.get(self.current).unwrap().clone() == lexer::Token::Fun {
-            self.current += 1;
+    pub fn print(&mut self) -> Result<ast::Stmt, ParseError> {
+        let mut values = vec![self.expression()?];
+        while self.matches(&[lexer::Token::Comma]) {
+            values.push(self.expression()?);
+        }
+        self.consume_terminator("Expect ';' after value.")?;
+        Ok(ast::Stmt::Print(values))
+    }
+
+    pub fn block(&mut self) -> Result<Vec<ast::Stmt>, ParseError> {
+        let mut statements = Vec::new();
+        while !self.is_at_end() && !self.check(&lexer::Token::RightBrace) {
+            statements.push(self.delaration()?);
+        }
+        self.consume(lexer::Token::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    pub fn delaration(&mut self) -> Result<ast::Stmt, ParseError> {
+        if self.matches(&[lexer::Token::Fun]) {
             return self.func_decl("function");
         }
-        if self.tokens.get(self.current).unwrap().clone() == lexer::Token::Var {
-            self.current += 1;
+        if self.matches(&[lexer::Token::Var]) {
             return self.var_decl();
         }
+        if self.matches(&[lexer::Token::Const]) {
+            return self.const_decl();
+        }
+        if self.matches(&[lexer::Token::Import]) {
+            return self.import_decl();
+        }
         self.statement()
     }
 
-    pub fn func_decl(&mut self, kind: &str) -> ast::Stmt {
-        if let lexer::Token::Identifier(name) = self.tokens.get(self.current).unwrap().clone() {
-            self.current += 1;
-            self.consume(lexer::Token::LeftParen);
+    pub fn func_decl(&mut self, kind: &str) -> Result<ast::Stmt, ParseError> {
+        if let lexer::Token::Identifier(_) = self.peek() {
+            let name = self.advance().clone();
+            self.consume(
+                lexer::Token::LeftParen,
+                &format!("Expect '(' after {} name.", kind),
+            )?;
             let mut params = Vec::new();
-            if self.tokens.get(self.current).unwrap().clone() != lexer::Token::RightParen {
-                if params.len() >= 255 {
-                    panic!("too many args");
-                }
-                params.push(self.tokens.get(self.current).unwrap().clone());
-                self.current += 1;
-                while self.tokens.get(self.current).unwrap().clone() == lexer::Token::Comma {
-                    self.current += 1;
-                    params.push(self.tokens.get(self.current).unwrap().clone());
-                    self.current += 1;
+            if !self.check(&lexer::Token::RightParen) {
+                params.push(self.parameter()?);
+                while self.matches(&[lexer::Token::Comma]) {
+                    params.push(self.parameter()?);
                 }
             }
-            self.consume(lexer::Token::RightParen);
-            let body = self.statement();
-            return ast::Stmt::Function {
-                name: lexer::Token::Identifier(name),
+            if params.len() > 255 {
+                return Err(self.error("Can't have more than 255 parameters."));
+            }
+            self.consume(lexer::Token::RightParen, "Expect ')' after parameters.")?;
+            // `fun f(x) => expr;` keeps the expression in `ret` and leaves the body empty.
+            if self.matches(&[lexer::Token::Arrow]) {
+                let ret = self.expression()?;
+                self.consume(
+                    lexer::Token::Semicolon,
+                    "Expect ';' after arrow function body.",
+                )?;
+                return Ok(ast::Stmt::Function {
+                    name,
+                    parameters: Some(params),
+                    body: Box::new(ast::Stmt::Block(Vec::new())),
+                    ret: Some(Box::new(ret)),
+                });
+            }
+            let body = self.statement()?;
+            return Ok(ast::Stmt::Function {
+                name,
                 parameters: Some(params),
                 body: Box::new(body),
                 ret: None,
-            };
+            });
         }
-        panic!("{:?}", self.tokens.get(self.current).unwrap().clone())
+        Err(self.error(&format!("Expect {} name, found {:?}", kind, self.peek())))
     }
 
-    pub fn var_decl(&mut self) -> ast::Stmt {
-        if let lexer::Token::Identifier(name) = self.tokens.get(self.current).unwrap().clone() {
-            self.current += 1;
-            if lexer::Token::Assign == self.tokens.get(self.current).unwrap().clone() {
-                self.current += 1;
-                let initializer = self.expression();
-                self.consume(lexer::Token::Semicolon);
-                return Stmt::Variable {
-                    name: lexer::Token::Identifier(name),
-                    initializer: Box::new(initializer),
-                };
+    pub fn parameter(&mut self) -> Result<lexer::Token, ParseError> {
+        if let lexer::Token::Identifier(_) = self.peek() {
+            Ok(self.advance().clone())
+        } else {
+            Err(self.error(&format!("Expect parameter name, found {:?}", self.peek())))
+        }
+    }
+
+    pub fn import_decl(&mut self) -> Result<ast::Stmt, ParseError> {
+        if let lexer::Token::String(path) = self.peek() {
+            let path = path.clone();
+            self.advance();
+            self.consume(lexer::Token::Semicolon, "Expect ';' after import path.")?;
+            Ok(Stmt::Import(path))
+        } else {
+            Err(self.error(&format!(
+                "Expect file path after 'import', found {:?}",
+                self.peek()
+            )))
+        }
+    }
+
+    // `var a = 1, b = a;` declares each name in turn in the current scope, so later initializers
+    // see the earlier names.
+    pub fn var_decl(&mut self) -> Result<ast::Stmt, ParseError> {
+        let mut declarations = vec![self.var_binding()?];
+        while self.matches(&[lexer::Token::Comma]) {
+            declarations.push(self.var_binding()?);
+        }
+        self.consume_terminator("Expect ';' after variable declaration.")?;
+        if declarations.len() == 1 {
+            Ok(declarations.pop().unwrap())
+        } else {
+            Ok(Stmt::VarList(declarations))
+        }
+    }
+
+    pub fn const_decl(&mut self) -> Result<ast::Stmt, ParseError> {
+        if let lexer::Token::Identifier(_) = self.peek() {
+            let name = self.advance().clone();
+            if !self.matches(&[lexer::Token::Assign]) {
+                return Err(self.error(&format!(
+                    "Expect '=' after const name, found {:?}",
+                    self.peek()
+                )));
             }
-            return Stmt::Variable {
-                name: lexer::Token::Identifier(name),
-                initializer: Box::new(ast::Expr::L(ast::Literal::Nil(()))),
-            };
+            let initializer = Box::new(self.expression()?);
+            self.consume_terminator("Expect ';' after const declaration.")?;
+            Ok(Stmt::Const { name, initializer })
+        } else {
+            Err(self.error(&format!("Expect const name, found {:?}", self.peek())))
+        }
+    }
+
+    fn var_binding(&mut self) -> Result<ast::Stmt, ParseError> {
+        if let lexer::Token::Identifier(_) = self.peek() {
+            let name = self.advance().clone();
+            let mut initializer = None;
+            if self.matches(&[lexer::Token::Assign]) {
+                initializer = Some(Box::new(self.expression()?));
+            }
+            Ok(Stmt::Variable { name, initializer })
         } else {
-            panic!()
+            Err(self.error(&format!("Expect variable name, found {:?}", self.peek())))
         }
     }
 
-    pub fn expression(&mut self) -> ast::Expr {
+    pub fn parse_expression(&mut self) -> Result<ast::Expr, ParseError> {
+        let expr = self.expression()?;
+        if !self.is_at_end() {
+            return Err(self.error(&format!(
+                "Expect end of expression, found {:?}",
+                self.peek()
+            )));
+        }
+        Ok(expr)
+    }
+
+    pub fn expression(&mut self) -> Result<ast::Expr, ParseError> {
         self.assignment()
     }
 
-    pub fn assignment(&mut self) -> ast::Expr {
-        let expr = self.or();
-        if self.tokens.get(self.current).unwrap() == &lexer::Token::Assign {
-            self.current += 1;
-            let value = self.assignment();
+    pub fn assignment(&mut self) -> Result<ast::Expr, ParseError> {
+        let expr = self.or()?;
+        if self.matches(&[lexer::Token::Assign]) {
+            let value = self.assignment()?;
 
-            if let ast::Expr::Variable { name } = expr {
-                return ast::Expr::Assign {
+            if let ast::Expr::Variable { name, .. } = expr {
+                return Ok(ast::Expr::Assign {
                     name,
                     value: Box::new(value),
-                };
+                });
+            } else if let ast::Expr::Index { object, index } = expr {
+                return Ok(ast::Expr::SetIndex {
+                    object,
+                    index,
+                    value: Box::new(value),
+                });
+            } else {
+                return Err(self.error(&format!("Invalid assignment target. {:?}", expr)));
+            }
+        }
+        if let lexer::Token::CompoundAssign(operator) = self.peek() {
+            let operator = operator.clone();
+            self.advance();
+            let position = self.position();
+            let value = self.assignment()?;
+
+            if let ast::Expr::Variable { name, .. } = expr {
+                return Ok(ast::Expr::Assign {
+                    name: name.clone(),
+                    value: Box::new(ast::Expr::Binary {
+                        left: Box::new(ast::Expr::Variable { name, position }),
+                        operator,
+                        right: Box::new(value),
+                        position,
+                    }),
+                });
             } else {
-                panic!("{:?} {:?}", expr, value)
+                return Err(self.error(&format!("Invalid assignment target. {:?}", expr)));
             }
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn or(&mut self) -> ast::Expr {
-        let mut expr = self.and();
-        while self.current < self.tokens.len()
-            && self.tokens.get(self.current).unwrap().clone() == lexer::Token::O(Operator::Or)
-        {
-            self.current += 1;
-            let right = self.and();
+    pub fn or(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.coalesce()?;
+        while self.matches(&[lexer::Token::O(Operator::Or)]) {
+            let right = self.coalesce()?;
             expr = ast::Expr::Logical {
                 left: Box::new(expr),
                 operator: Operator::Or,
                 right: Box::new(right),
             }
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn and(&mut self) -> ast::Expr {
-        let mut expr = self.equality();
-        while self.current < self.tokens.len()
-            && self.tokens.get(self.current).unwrap().clone() == lexer::Token::O(Operator::And)
-        {
-            self.current += 1;
-            let right = self.and();
+    pub fn coalesce(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.and()?;
+        while self.matches(&[lexer::Token::O(Operator::Coalesce)]) {
+            let right = self.and()?;
+            expr = ast::Expr::Logical {
+                left: Box::new(expr),
+                operator: Operator::Coalesce,
+                right: Box::new(right),
+            }
+        }
+        Ok(expr)
+    }
+
+    pub fn and(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.equality()?;
+        while self.matches(&[lexer::Token::O(Operator::And)]) {
+            let right = self.equality()?;
             expr = ast::Expr::Logical {
                 left: Box::new(expr),
                 operator: Operator::And,
                 right: Box::new(right),
             }
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn equality(&mut self) -> ast::Expr {
-        let mut expr = self.comparsion();
+    pub fn equality(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.comparsion()?;
 
-        while self.current < self.tokens.len()
-            && (self.tokens.get(self.current).unwrap().clone() == lexer::Token::O(Operator::Equal)
-                || self.tokens.get(self.current).unwrap().clone()
-                    == lexer::Token::O(Operator::NotEqual))
-        {
-            self.current += 1;
-            if let lexer::Token::O(operator) = self.previous() {
-                let right = self.comparsion();
-                expr = ast::Expr::Binary {
-                    left: Box::new(expr),
-                    operator,
-                    right: Box::new(right),
-                }
-            } else {
-                panic!()
+        while self.matches(&[
+            lexer::Token::O(Operator::Equal),
+            lexer::Token::O(Operator::NotEqual),
+        ]) {
+            let operator = self.previous_operator();
+            let position = self.position();
+            let right = self.comparsion()?;
+            expr = ast::Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+                position,
             }
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn comparsion(&mut self) -> ast::Expr {
-        let mut expr = self.addition();
+    // Lox has no Python-style chaining and `(1 < 2) < 3` would compare a bool with a number, so a
+    // second comparison operator is rejected outright.
+    pub fn comparsion(&mut self) -> Result<ast::Expr, ParseError> {
+        let comparisons = [
+            lexer::Token::O(Operator::GreaterThan),
+            lexer::Token::O(Operator::GreaterThanOrEqual),
+            lexer::Token::O(Operator::LessThan),
+            lexer::Token::O(Operator::LessThanOrEqual),
+        ];
+        let expr = self.bit_or()?;
 
-        while self.current < self.tokens.len()
-            && (self.tokens.get(self.current).unwrap().clone()
-                == lexer::Token::O(Operator::GreaterThan)
-                || self.tokens.get(self.current).unwrap().clone()
-                    == lexer::Token::O(Operator::GreaterThanOrEqual)
-                || self.tokens.get(self.current).unwrap().clone()
-                    == lexer::Token::O(Operator::LessThan)
-                || self.tokens.get(self.current).unwrap().clone()
-                    == lexer::Token::O(Operator::LessThanOrEqual))
-        {
-            self.current += 1;
-            if let lexer::Token::O(operator) = self.previous() {
-                let right = self.addition();
-                expr = ast::Expr::Binary {
-                    left: Box::new(expr),
-                    operator,
-                    right: Box::new(right),
-                }
-            } else {
-                panic!()
+        if self.matches(&comparisons) {
+            let operator = self.previous_operator();
+            let position = self.position();
+            let right = self.bit_or()?;
+            if comparisons.contains(self.peek()) {
+                return Err(self.error(&format!("Comparisons can't be chained. {:?}", self.peek())));
             }
+            return Ok(ast::Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+                position,
+            });
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn addition(&mut self) -> ast::Expr {
-        let mut expr = self.multiplication();
+    pub fn bit_or(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.bit_xor()?;
 
-        while self.current < self.tokens.len()
-            && (self.tokens.get(self.current).unwrap().clone() == lexer::Token::O(Operator::Minus)
-                || self.tokens.get(self.current).unwrap().clone()
-                    == lexer::Token::O(Operator::Plus))
-        {
-            self.current += 1;
-            if let lexer::Token::O(operator) = self.previous() {
-                let right = self.multiplication();
-                expr = ast::Expr::Binary {
-                    left: Box::new(expr),
-                    operator,
-                    right: Box::new(right),
-                }
-            } else {
-                panic!()
+        while self.matches(&[lexer::Token::O(Operator::BitOr)]) {
+            let operator = self.previous_operator();
+            let position = self.position();
+            let right = self.bit_xor()?;
+            expr = ast::Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+                position,
             }
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn multiplication(&mut self) -> ast::Expr {
-        let mut expr = self.unary();
+    pub fn bit_xor(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.bit_and()?;
 
-        while self.current < self.tokens.len()
-            && (self.tokens.get(self.current).unwrap().clone() == lexer::Token::O(Operator::Slash)
-                || self.tokens.get(self.current).unwrap().clone()
-                    == lexer::Token::O(Operator::Star))
-        {
-            self.current += 1;
-            if let lexer::Token::O(operator) = self.previous() {
-                let right = self.unary();
-                expr = ast::Expr::Binary {
-                    left: Box::new(expr),
-                    operator,
-                    right: Box::new(right),
-                }
-            } else {
-                panic!()
+        while self.matches(&[lexer::Token::O(Operator::BitXor)]) {
+            let operator = self.previous_operator();
+            let position = self.position();
+            let right = self.bit_and()?;
+            expr = ast::Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+                position,
             }
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn unary(&mut self) -> ast::Expr {
-        if self.tokens.get(self.current).unwrap().clone() == lexer::Token::O(Operator::Not)
-            || self.tokens.get(self.current).unwrap().clone() == lexer::Token::O(Operator::Minus)
-        {
-            self.current += 1;
-            if let lexer::Token::O(operator) = self.previous() {
-                let right = self.unary();
-                ast::Expr::Unary {
-                    operator,
-                    right: Box::new(right),
-                }
-            } else {
-                panic!()
+    pub fn bit_and(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.shift()?;
+
+        while self.matches(&[lexer::Token::O(Operator::BitAnd)]) {
+            let operator = self.previous_operator();
+            let position = self.position();
+            let right = self.shift()?;
+            expr = ast::Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+                position,
+            }
+        }
+        Ok(expr)
+    }
+
+    pub fn shift(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.addition()?;
+
+        while self.matches(&[
+            lexer::Token::O(Operator::ShiftLeft),
+            lexer::Token::O(Operator::ShiftRight),
+        ]) {
+            let operator = self.previous_operator();
+            let position = self.position();
+            let right = self.addition()?;
+            expr = ast::Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+                position,
+            }
+        }
+        Ok(expr)
+    }
+
+    pub fn addition(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.multiplication()?;
+
+        while self.matches(&[
+            lexer::Token::O(Operator::Minus),
+            lexer::Token::O(Operator::Plus),
+        ]) {
+            let operator = self.previous_operator();
+            let position = self.position();
+            let right = self.multiplication()?;
+            expr = ast::Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+                position,
+            }
+        }
+        Ok(expr)
+    }
+
+    pub fn multiplication(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.unary()?;
+
+        while self.matches(&[
+            lexer::Token::O(Operator::Slash),
+            lexer::Token::O(Operator::Star),
+        ]) {
+            let operator = self.previous_operator();
+            let position = self.position();
+            let right = self.unary()?;
+            expr = ast::Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+                position,
             }
+        }
+        Ok(expr)
+    }
+
+    pub fn unary(&mut self) -> Result<ast::Expr, ParseError> {
+        if self.matches(&[
+            lexer::Token::O(Operator::Not),
+            lexer::Token::O(Operator::Minus),
+        ]) {
+            let operator = self.previous_operator();
+            let position = self.position();
+            let right = self.unary()?;
+            Ok(ast::Expr::Unary {
+                operator,
+                right: Box::new(right),
+                position,
+            })
+        } else if self.matches(&[lexer::Token::Increment, lexer::Token::Decrement]) {
+            let operator = if self.previous() == &lexer::Token::Increment {
+                Operator::Plus
+            } else {
+                Operator::Minus
+            };
+            let target = self.unary()?;
+            self.increment(target, operator)
         } else {
-            self.call()
+            self.power()
         }
     }
 
-    pub fn call(&mut self) -> ast::Expr {
-        let mut expr = self.primary();
+    // `**` binds tighter than prefix operators and its right operand is a unary, so `-2 ** 2` is
+    // `-(2 ** 2)` and `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+    pub fn power(&mut self) -> Result<ast::Expr, ParseError> {
+        let expr = self.call()?;
+        if self.matches(&[lexer::Token::O(Operator::Power)]) {
+            let position = self.position();
+            let right = self.unary()?;
+            return Ok(ast::Expr::Binary {
+                left: Box::new(expr),
+                operator: Operator::Power,
+                right: Box::new(right),
+                position,
+            });
+        }
+        Ok(expr)
+    }
+
+    // `++x` desugars to `x = x + 1` and evaluates to the new value. `x++` needs the old value, so
+    // it gets its own node rather than undoing the step, which isn't exact for fractions.
+    pub fn increment(
+        &mut self,
+        target: ast::Expr,
+        operator: Operator,
+    ) -> Result<ast::Expr, ParseError> {
+        if let ast::Expr::Variable { name, position } = target {
+            Ok(ast::Expr::Assign {
+                name: name.clone(),
+                value: Box::new(ast::Expr::Binary {
+                    left: Box::new(ast::Expr::Variable { name, position }),
+                    operator,
+                    right: Box::new(ast::Expr::L(ast::Literal::Float(1.0))),
+                    position,
+                }),
+            })
+        } else {
+            Err(self.error("Invalid increment target."))
+        }
+    }
+
+    pub fn call(&mut self) -> Result<ast::Expr, ParseError> {
+        let mut expr = self.primary()?;
 
         loop {
-            if self.tokens.get(self.current).unwrap().clone() == lexer::Token::LeftParen {
-                self.current += 1;
-                expr = self.finish_call(expr)
+            if self.matches(&[lexer::Token::LeftParen]) {
+                expr = self.finish_call(expr)?
+            } else if self.matches(&[lexer::Token::LeftBracket]) {
+                let index = self.expression()?;
+                self.consume(lexer::Token::RightBracket, "Expect ']' after index.")?;
+                expr = ast::Expr::Index {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                }
             } else {
                 break;
             }
         }
-        expr
+
+        if self.check(&lexer::Token::Increment) || self.check(&lexer::Token::Decrement) {
+            if let ast::Expr::Variable { name, .. } = expr {
+                let operator = if self.advance() == &lexer::Token::Increment {
+                    Operator::Plus
+                } else {
+                    Operator::Minus
+                };
+                return Ok(ast::Expr::Postfix {
+                    name,
+                    operator,
+                    position: self.position(),
+                });
+            }
+            return Err(self.error("Invalid increment target."));
+        }
+        Ok(expr)
     }
 
-    pub fn finish_call(&mut self, callee: ast::Expr) -> ast::Expr {
+    pub fn finish_call(&mut self, callee: ast::Expr) -> Result<ast::Expr, ParseError> {
+        let position = self.position();
         let mut arguments = Vec::new();
-        if self.tokens.get(self.current).unwrap().clone() != lexer::Token::RightParen {
-            if arguments.len() >= 255 {
-                panic!("too many args");
-            }
-            arguments.push(self.expression());
-            while self.tokens.get(self.current).unwrap().clone() == lexer::Token::Comma {
-                self.current += 1;
-                arguments.push(self.expression());
+        if !self.check(&lexer::Token::RightParen) {
+            arguments.push(self.expression()?);
+            while self.matches(&[lexer::Token::Comma]) {
+                arguments.push(self.expression()?);
             }
         }
-        self.consume(lexer::Token::RightParen);
-        ast::Expr::Call {
+        if arguments.len() > 255 {
+            return Err(self.error("Can't have more than 255 arguments."));
+        }
+        self.consume(lexer::Token::RightParen, "Expect ')' after arguments.")?;
+        Ok(ast::Expr::Call {
             callee: Box::new(callee),
             arguments,
-        }
+            position,
+        })
     }
 
-    pub fn primary(&mut self) -> ast::Expr {
-        let cur = self.tokens.get(self.current).unwrap().clone();
-        self.current += 1;
-        if let lexer::Token::Identifier(_) = cur {
-            ast::Expr::Variable { name: cur.clone() }
-        } else if cur == lexer::Token::LeftParen {
-            let expr = self.expression();
-            self.consume(lexer::Token::RightParen);
-            ast::Expr::Grouping {
-                expression: Box::new(expr),
+    // Looks at the token before consuming it, so a missing operand is reported where it should
+    // have been and the parser never steps past `Eof`.
+    pub fn primary(&mut self) -> Result<ast::Expr, ParseError> {
+        let literal = match self.peek() {
+            lexer::Token::Bool(b) => Some(ast::Literal::Bool(*b)),
+            lexer::Token::Nil(_) => Some(ast::Literal::Nil(())),
+            lexer::Token::Float(f) => Some(ast::Literal::Float(*f)),
+            lexer::Token::Int(i) => Some(ast::Literal::Float(*i as f64)),
+            lexer::Token::String(s) => Some(ast::Literal::String(s.clone().into())),
+            _ => None,
+        };
+        if let Some(literal) = literal {
+            self.advance();
+            return Ok(ast::Expr::L(literal));
+        }
+        if let lexer::Token::Identifier(_) = self.peek() {
+            let name = self.advance().clone();
+            return Ok(ast::Expr::Variable {
+                name,
+                position: self.position(),
+            });
+        }
+        if self.matches(&[lexer::Token::LeftBracket]) {
+            let mut elements = Vec::new();
+            if !self.check(&lexer::Token::RightBracket) {
+                elements.push(self.expression()?);
+                while self.matches(&[lexer::Token::Comma]) {
+                    elements.push(self.expression()?);
+                }
             }
-        } else {
-            match cur {
-                lexer::Token::Bool(b) => ast::Expr::L(ast::Literal::Bool(b)),
-                lexer::Token::Nil(n) => ast::Expr::L(ast::Literal::Nil(n)),
-                lexer::Token::Float(f) => ast::Expr::L(ast::Literal::Float(f)),
-                lexer::Token::String(s) => ast::Expr::L(ast::Literal::String(s)),
-                _ => panic!("{:?}", cur),
+            self.consume(
+                lexer::Token::RightBracket,
+                "Expect ']' after list elements.",
+            )?;
+            return Ok(ast::Expr::List { elements });
+        }
+        if self.matches(&[lexer::Token::LeftBrace]) {
+            let mut entries = Vec::new();
+            if !self.check(&lexer::Token::RightBrace) {
+                entries.push(self.entry()?);
+                while self.matches(&[lexer::Token::Comma]) {
+                    entries.push(self.entry()?);
+                }
             }
+            self.consume(lexer::Token::RightBrace, "Expect '}' after map entries.")?;
+            return Ok(ast::Expr::Map { entries });
+        }
+        if self.matches(&[lexer::Token::LeftParen]) {
+            let expr = self.expression()?;
+            self.consume(lexer::Token::RightParen, "Expect ')' after expression.")?;
+            return Ok(ast::Expr::Grouping {
+                expression: Box::new(expr),
+            });
         }
+        Err(self.error("Expect expression."))
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.check(&lexer::Token::Eof)
+    }
+
+    pub fn entry(&mut self) -> Result<(ast::Expr, ast::Expr), ParseError> {
+        let key = self.expression()?;
+        self.consume(lexer::Token::Colon, "Expect ':' after map key.")?;
+        Ok((key, self.expression()?))
     }
 
-    pub fn previous(&mut self) -> lexer::Token {
-        self.tokens.get(self.current - 1).unwrap().clone()
+    pub fn peek(&self) -> &lexer::Token {
+        self.tokens.get(self.current).unwrap()
     }
 
-    pub fn consume(&mut self, token: lexer::Token) {
-        if self.tokens.get(self.current).unwrap().clone() == token {
+    pub fn check(&self, token: &lexer::Token) -> bool {
+        self.peek() == token
+    }
+
+    // Stays on `Eof` so that nothing reads past the end of the tokens.
+    pub fn advance(&mut self) -> &lexer::Token {
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    pub fn matches(&mut self, tokens: &[lexer::Token]) -> bool {
+        if tokens.iter().any(|token| self.check(token)) {
             self.current += 1;
+            true
         } else {
-            panic!(
-                "token {:?} current {:?}",
-                token,
-                self.tokens.get(self.current)
-            )
+            false
         }
     }
 
+    pub fn previous(&self) -> &lexer::Token {
+        self.tokens.get(self.current - 1).unwrap()
+    }
+
+    // Where the previous token starts, if the parser was given positions.
+    pub fn position(&self) -> Option<lexer::Position> {
+        let index = self.current - 1;
+        Some((*self.lines.get(index)?, *self.columns.get(index)?))
+    }
+
+    // Only called right after matching an operator token, so anything else is a parser bug.
+    pub fn previous_operator(&self) -> Operator {
+        if let lexer::Token::O(operator) = self.previous() {
+            operator.clone()
+        } else {
+            panic!("Expect operator, found {:?}", self.previous())
+        }
+    }
+
+    pub fn consume(&mut self, token: lexer::Token, message: &str) -> Result<(), ParseError> {
+        if self.check(&token) {
+            self.current += 1;
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    // A statement's `;` may be left off right before a `}` or the end of input, which only warns.
+    pub fn consume_terminator(&mut self, message: &str) -> Result<(), ParseError> {
+        if !self.matches(&[lexer::Token::Semicolon]) {
+            if self.check(&lexer::Token::RightBrace) || self.is_at_end() {
+                let warning = self.error(&format!("Warning: missing ';' before {}.", self.peek()));
+                self.warnings.push(warning);
+            } else {
+                self.consume(lexer::Token::Semicolon, message)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn print_current(&self) {
-        println!("{:?}", self.tokens.get(self.current))
+        println!("{:?}", self.peek())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::lexer::lexer;
+    use crate::intern::Symbol;
+    use crate::lexer::{lex, lex_with_positions, Operator, Token};
+    use crate::parser::ast::{Expr, Literal, Stmt};
+    use crate::parser::error::ParseError;
     use crate::parser::parser::Parser;
 
+    fn parse_errors(input: &[char]) -> Vec<ParseError> {
+        let tokens = lex(input).unwrap();
+        Parser::new(tokens).parse().unwrap_err()
+    }
+
     #[test]
     fn parse_test() {
         let input: Vec<char> = "nil;".chars().collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.expression();
+        let e = p.expression().unwrap();
         println!("{:#?}", e);
     }
 
     #[test]
     fn parse_print() {
         let input: Vec<char> = "print 5;".chars().collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
     }
 
     #[test]
     fn parse_var() {
         let input: Vec<char> = "var x = 5;".chars().collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
     }
 
     #[test]
     fn parse_block() {
         let input: Vec<char> = "{var x = 5;} {var y = 10;} {print y;}".chars().collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
     }
 
@@ -516,18 +966,135 @@ mod tests {
         let input: Vec<char> = "if (x < 5) { print x; } else { print 5; }"
             .chars()
             .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
     }
 
+    #[test]
+    fn parse_dangling_else() {
+        let input: Vec<char> = "if (a) if (b) print 1; else print 2;".chars().collect();
+        let tokens = lex(&input).unwrap();
+        let variable = |name: &str| {
+            Box::new(Expr::Variable {
+                name: Token::Identifier(Symbol::intern(name)),
+                position: None,
+            })
+        };
+        let print = |n| Stmt::Print(vec![Expr::L(Literal::Float(n))]);
+        assert_eq!(
+            Parser::new(tokens).parse().unwrap(),
+            vec![Stmt::If {
+                condition: variable("a"),
+                then_branch: Box::new(Stmt::If {
+                    condition: variable("b"),
+                    then_branch: Box::new(print(1.0)),
+                    else_branch: Some(Box::new(print(2.0))),
+                }),
+                else_branch: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_missing_semicolon() {
+        let parse = |source: &str| {
+            let input: Vec<char> = source.chars().collect();
+            let (tokens, positions) = lex_with_positions(&input).unwrap();
+            let mut parser = Parser::with_positions(tokens, positions);
+            let stmts = parser.parse().unwrap();
+            let warnings: Vec<String> = parser.warnings().iter().map(|w| w.to_string()).collect();
+            (stmts, warnings)
+        };
+        let print = |n| Stmt::Print(vec![Expr::L(Literal::Float(n))]);
+        assert_eq!(
+            parse("{ print 1 }"),
+            (
+                vec![Stmt::Block(vec![print(1.0)])],
+                vec!["[line 1] Warning: missing ';' before }.".to_string()]
+            )
+        );
+        assert_eq!(
+            parse("print 2;\nprint 1"),
+            (
+                vec![print(2.0), print(1.0)],
+                vec!["[line 2] Warning: missing ';' before EOF.".to_string()]
+            )
+        );
+        assert_eq!(parse("print 1;").1, Vec::<String>::new());
+        assert_eq!(parse("var a").0, parse("var a;").0);
+        assert_eq!(parse("{ a = 1 }").0, parse("{ a = 1; }").0);
+    }
+
+    #[test]
+    fn parse_missing_semicolon_mid_block() {
+        let input: Vec<char> = "{ print 1 print 2; }".chars().collect();
+        assert!(!parse_errors(&input).is_empty());
+    }
+
+    #[test]
+    fn parse_var_list() {
+        let input: Vec<char> = "var a = 1, b;".chars().collect();
+        let tokens = lex(&input).unwrap();
+        assert_eq!(
+            Parser::new(tokens).parse().unwrap(),
+            vec![Stmt::VarList(vec![
+                Stmt::Variable {
+                    name: Token::Identifier(Symbol::intern("a")),
+                    initializer: Some(Box::new(Expr::L(Literal::Float(1.0)))),
+                },
+                Stmt::Variable {
+                    name: Token::Identifier(Symbol::intern("b")),
+                    initializer: None,
+                },
+            ])]
+        );
+    }
+
+    #[test]
+    fn parse_const() {
+        let input: Vec<char> = "const a = 1;".chars().collect();
+        let tokens = lex(&input).unwrap();
+        assert_eq!(
+            Parser::new(tokens).parse().unwrap(),
+            vec![Stmt::Const {
+                name: Token::Identifier(Symbol::intern("a")),
+                initializer: Box::new(Expr::L(Literal::Float(1.0))),
+            }]
+        );
+        let input: Vec<char> = "const a;".chars().collect();
+        assert_eq!(
+            parse_errors(&input)[0].message,
+            "Expect '=' after const name, found Semicolon"
+        );
+    }
+
+    #[test]
+    fn parse_var_nil() {
+        let input: Vec<char> = "var a; var b = nil;".chars().collect();
+        let tokens = lex(&input).unwrap();
+        assert_eq!(
+            Parser::new(tokens).parse().unwrap(),
+            vec![
+                Stmt::Variable {
+                    name: Token::Identifier(Symbol::intern("a")),
+                    initializer: None,
+                },
+                Stmt::Variable {
+                    name: Token::Identifier(Symbol::intern("b")),
+                    initializer: Some(Box::new(Expr::L(Literal::Nil(())))),
+                },
+            ]
+        );
+    }
+
     #[test]
     fn parse_logical() {
         let input: Vec<char> = "a and b or c;".chars().collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
     }
 
@@ -541,9 +1108,9 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
     }
 
@@ -558,12 +1125,25 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
     }
 
+    #[test]
+    fn parse_empty_for() {
+        let input: Vec<char> = "for (;;) print 1;".chars().collect();
+        let tokens = lex(&input).unwrap();
+        assert_eq!(
+            Parser::new(tokens).parse().unwrap(),
+            vec![Stmt::While {
+                condition: Box::new(Expr::L(Literal::Bool(true))),
+                body: Box::new(Stmt::Print(vec![Expr::L(Literal::Float(1.0))])),
+            }]
+        );
+    }
+
     #[test]
     fn clock() {
         let input: Vec<char> = r#"
@@ -571,9 +1151,9 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
     }
 
@@ -588,9 +1168,228 @@ mod tests {
         "#
         .chars()
         .collect();
-        let tokens = lexer().parse(&input).unwrap();
+        let tokens = lex(&input).unwrap();
         let mut p = Parser::new(tokens);
-        let e = p.parse();
+        let e = p.parse().unwrap();
         println!("{:#?}", e);
     }
+
+    #[test]
+    fn parse_chained_and() {
+        let input: Vec<char> = "a and b and c;".chars().collect();
+        let tokens = lex(&input).unwrap();
+        let e = Parser::new(tokens).parse().unwrap();
+        let var = |name: &str| {
+            Box::new(Expr::Variable {
+                name: Token::Identifier(Symbol::intern(name)),
+                position: None,
+            })
+        };
+        assert_eq!(
+            e,
+            vec![Stmt::Expr(Box::new(Expr::Logical {
+                left: Box::new(Expr::Logical {
+                    left: var("a"),
+                    operator: Operator::And,
+                    right: var("b"),
+                }),
+                operator: Operator::And,
+                right: var("c"),
+            }))]
+        );
+    }
+
+    #[test]
+    fn parse_compound_assign() {
+        let input: Vec<char> = "a *= 2;".chars().collect();
+        let tokens = lex(&input).unwrap();
+        let e = Parser::new(tokens).parse().unwrap();
+        let a = Token::Identifier(Symbol::intern("a"));
+        assert_eq!(
+            e,
+            vec![Stmt::Expr(Box::new(Expr::Assign {
+                name: a.clone(),
+                value: Box::new(Expr::Binary {
+                    left: Box::new(Expr::Variable {
+                        name: a,
+                        position: None,
+                    }),
+                    operator: Operator::Star,
+                    right: Box::new(Expr::L(Literal::Float(2.0))),
+                    position: None,
+                }),
+            }))]
+        );
+    }
+
+    #[test]
+    fn parse_map_vs_block() {
+        let input: Vec<char> = r#"{ var m = {"a": 1, "b": {}}; }"#.chars().collect();
+        let tokens = lex(&input).unwrap();
+        let e = Parser::new(tokens).parse().unwrap();
+        assert_eq!(
+            e,
+            vec![Stmt::Block(vec![Stmt::Variable {
+                name: Token::Identifier(Symbol::intern("m")),
+                initializer: Some(Box::new(Expr::Map {
+                    entries: vec![
+                        (
+                            Expr::L(Literal::String("a".to_string().into())),
+                            Expr::L(Literal::Float(1.0))
+                        ),
+                        (
+                            Expr::L(Literal::String("b".to_string().into())),
+                            Expr::Map { entries: vec![] }
+                        ),
+                    ]
+                })),
+            }])]
+        );
+    }
+
+    #[test]
+    fn compound_assign_non_variable() {
+        let input: Vec<char> = "1 += 2;".chars().collect();
+        assert!(parse_errors(&input)[0]
+            .message
+            .starts_with("Invalid assignment target."));
+    }
+
+    #[test]
+    fn increment_non_variable() {
+        for source in &["++1;", "print 1++;"] {
+            let input: Vec<char> = source.chars().collect();
+            assert_eq!(parse_errors(&input)[0].message, "Invalid increment target.");
+        }
+    }
+
+    #[test]
+    fn call_too_many_args() {
+        let args: Vec<String> = (0..256).map(|i| i.to_string()).collect();
+        let input: Vec<char> = format!("f({});", args.join(", ")).chars().collect();
+        assert!(parse_errors(&input)[0]
+            .message
+            .starts_with("Can't have more than 255 arguments."));
+    }
+
+    #[test]
+    fn function_non_identifier_param() {
+        let input: Vec<char> = "fun f(1, 2) {}".chars().collect();
+        assert!(parse_errors(&input)[0]
+            .message
+            .starts_with("Expect parameter name"));
+    }
+
+    #[test]
+    fn function_too_many_params() {
+        let params: Vec<String> = (0..256).map(|i| format!("p{}", i)).collect();
+        let input: Vec<char> = format!("fun f({}) {{}}", params.join(", "))
+            .chars()
+            .collect();
+        assert!(parse_errors(&input)[0]
+            .message
+            .starts_with("Can't have more than 255 parameters."));
+    }
+
+    #[test]
+    fn chained_comparison() {
+        let input: Vec<char> = "1 < 2 < 3;".chars().collect();
+        assert!(parse_errors(&input)[0]
+            .message
+            .starts_with("Comparisons can't be chained."));
+    }
+
+    #[test]
+    fn recover_from_errors() {
+        let input: Vec<char> = "print 1;\nvar = 2;\nprint 3;\nprint (4;\nprint 5;"
+            .chars()
+            .collect();
+        let (tokens, positions) = lex_with_positions(&input).unwrap();
+        let errors = Parser::with_positions(tokens.clone(), positions)
+            .parse()
+            .unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+            vec![Some(2), Some(4)]
+        );
+        assert!(errors[0].to_string().starts_with("[line 2] "));
+
+        let (stmts, errors) = Parser::new(tokens).parse_partial();
+        assert_eq!(errors.len(), 2);
+        let print = |n| Stmt::Print(vec![Expr::L(Literal::Float(n))]);
+        assert_eq!(stmts, vec![print(1.0), print(3.0), print(5.0)]);
+    }
+
+    #[test]
+    fn parenthesized_comparison() {
+        let input: Vec<char> = "(1 < 2) == (2 >= 3);".chars().collect();
+        let tokens = lex(&input).unwrap();
+        assert_eq!(Parser::new(tokens).parse().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parse_expression() {
+        let input: Vec<char> = "1 + 2".chars().collect();
+        let tokens = lex(&input).unwrap();
+        assert_eq!(
+            Parser::new(tokens).parse_expression(),
+            Ok(Expr::Binary {
+                left: Box::new(Expr::L(Literal::Float(1.0))),
+                operator: Operator::Plus,
+                right: Box::new(Expr::L(Literal::Float(2.0))),
+                position: None,
+            })
+        );
+
+        let input: Vec<char> = "1 + 2 garbage".chars().collect();
+        let tokens = lex(&input).unwrap();
+        assert_eq!(
+            Parser::new(tokens).parse_expression(),
+            Err(ParseError::new(
+                "Expect end of expression, found Identifier(\"garbage\")"
+            ))
+        );
+    }
+
+    #[test]
+    fn truncated_input() {
+        for source in &["print 1 +", "var a = ", "(", "if (", "print a["] {
+            let input: Vec<char> = source.chars().collect();
+            assert_eq!(parse_errors(&input).len(), 1, "{}", source);
+        }
+    }
+
+    #[test]
+    fn error_messages() {
+        for (source, message, column) in &[
+            ("print;", "Expect expression.", 6),
+            ("print 1 +", "Expect expression.", 10),
+            ("hex(1e30);", "Expect ')' after arguments.", 6),
+            ("if (true print 1;", "Expect ')' after if condition.", 10),
+            ("var a = [1, 2;", "Expect ']' after list elements.", 14),
+        ] {
+            let input: Vec<char> = source.chars().collect();
+            let (tokens, positions) = lex_with_positions(&input).unwrap();
+            let errors = Parser::with_positions(tokens, positions)
+                .parse()
+                .unwrap_err();
+            assert_eq!(errors[0].message, *message);
+            assert_eq!(errors[0].line, Some(1), "{}", source);
+            assert_eq!(errors[0].column, Some(*column), "{}", source);
+        }
+    }
+
+    #[test]
+    fn duplicate_default() {
+        let input: Vec<char> = "switch (1) { default: print 1; default: print 2; }\nprint 3;"
+            .chars()
+            .collect();
+        let (tokens, positions) = lex_with_positions(&input).unwrap();
+        let (stmts, errors) = Parser::with_positions(tokens, positions).parse_partial();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Duplicate 'default' in switch.");
+        assert_eq!((errors[0].line, errors[0].column), (Some(1), Some(32)));
+        assert_eq!(stmts.len(), 2);
+        assert_eq!(stmts[1], Stmt::Print(vec![Expr::L(Literal::Float(3.0))]));
+    }
 }
@@ -0,0 +1,894 @@
+use crate::lexer::{lox_escape, Operator, Token};
+use crate::parser::ast::{Expr, ExprData, ExprVisitor, Literal, Stmt, StmtData, StmtVisitor};
+
+pub struct AstPrinter;
+
+impl AstPrinter {
+    pub fn print(&mut self, stmts: &[Stmt]) -> String {
+        stmts
+            .iter()
+            .map(|stmt| stmt.accept(self))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn print_expr(&mut self, expr: &Expr) -> String {
+        expr.accept(self)
+    }
+
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> String {
+        let mut s = format!("({}", name);
+        for expr in exprs {
+            s.push(' ');
+            s.push_str(&expr.accept(self));
+        }
+        s.push(')');
+        s
+    }
+
+    fn parenthesize_stmts(&mut self, name: &str, stmts: &[&Stmt]) -> String {
+        let mut s = format!("({}", name);
+        for stmt in stmts {
+            s.push(' ');
+            s.push_str(&stmt.accept(self));
+        }
+        s.push(')');
+        s
+    }
+}
+
+impl ExprVisitor for AstPrinter {
+    type Result = String;
+
+    fn visit_literal(&mut self, expr: &Literal) -> Self::Result {
+        match expr {
+            Literal::Float(f) => format!("{}", f),
+            Literal::Bool(b) => format!("{}", b),
+            Literal::String(s) => lox_escape(s),
+            Literal::Nil(_) => "nil".to_string(),
+        }
+    }
+
+    fn visit_unary(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Unary {
+            operator, right, ..
+        } = expr
+        {
+            return self.parenthesize(&operator.to_string(), &[right]);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_binary(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Binary {
+            left,
+            operator,
+            right,
+            ..
+        } = expr
+        {
+            return self.parenthesize(&operator.to_string(), &[left, right]);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_logical(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Logical {
+            left,
+            operator,
+            right,
+        } = expr
+        {
+            return self.parenthesize(&operator.to_string(), &[left, right]);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_grouping(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Grouping { expression } = expr {
+            return self.parenthesize("group", &[expression]);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_assign(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Assign { name, value } = expr {
+            return self.parenthesize(&format!("= {}", name), &[value]);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_postfix(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Postfix { name, operator, .. } = expr {
+            return format!("({}{}{})", name, operator, operator);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_variable(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Variable { name, .. } = expr {
+            return name.to_string();
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_call(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Call {
+            callee, arguments, ..
+        } = expr
+        {
+            let mut exprs = vec![callee.as_ref()];
+            exprs.extend(arguments.iter());
+            return self.parenthesize("call", &exprs);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_list(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::List { elements } = expr {
+            return self.parenthesize("list", &elements.iter().collect::<Vec<&Expr>>());
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_map(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Map { entries } = expr {
+            let mut s = "(map".to_string();
+            for (key, value) in entries {
+                s.push(' ');
+                let key = key.accept(self);
+                s.push_str(&self.parenthesize(&key, &[value]));
+            }
+            s.push(')');
+            return s;
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_index(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Index { object, index } = expr {
+            return self.parenthesize("index", &[object, index]);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_set_index(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::SetIndex {
+            object,
+            index,
+            value,
+        } = expr
+        {
+            let target = self.parenthesize("index", &[object, index]);
+            return self.parenthesize(&format!("= {}", target), &[value]);
+        }
+        panic!("{:?}", expr)
+    }
+}
+
+impl StmtVisitor for AstPrinter {
+    type Result = String;
+
+    fn visit_expr_stmt(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Expr(expr) = stmt {
+            return self.parenthesize(";", &[expr]);
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_print(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Print(exprs) = stmt {
+            return self.parenthesize("print", &exprs.iter().collect::<Vec<&Expr>>());
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_var_decl(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Variable { name, initializer } = stmt {
+            return match initializer {
+                Some(initializer) => self.parenthesize(&format!("var {} =", name), &[initializer]),
+                None => self.parenthesize(&format!("var {}", name), &[]),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_var_list(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::VarList(stmts) = stmt {
+            return self.parenthesize_stmts("vars", &stmts.iter().collect::<Vec<&Stmt>>());
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_const(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Const { name, initializer } = stmt {
+            return self.parenthesize(&format!("const {} =", name), &[initializer]);
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_block(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Block(stmts) = stmt {
+            return self.parenthesize_stmts("block", &stmts.iter().collect::<Vec<&Stmt>>());
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_if(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } = stmt
+        {
+            let condition = condition.accept(self);
+            let mut stmts = vec![then_branch.as_ref()];
+            if let Some(else_branch) = else_branch {
+                stmts.push(else_branch);
+            }
+            return self.parenthesize_stmts(&format!("if {}", condition), &stmts);
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_while(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::While { condition, body } = stmt {
+            let condition = condition.accept(self);
+            return self.parenthesize_stmts(&format!("while {}", condition), &[body]);
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_switch(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Switch {
+            subject,
+            cases,
+            default,
+        } = stmt
+        {
+            let mut s = format!("(switch {}", subject.accept(self));
+            for (value, body) in cases {
+                let value = value.accept(self);
+                let case = self.parenthesize_stmts(
+                    &format!("case {}", value),
+                    &body.iter().collect::<Vec<&Stmt>>(),
+                );
+                s.push(' ');
+                s.push_str(&case);
+            }
+            if let Some(body) = default {
+                s.push(' ');
+                s.push_str(
+                    &self.parenthesize_stmts("default", &body.iter().collect::<Vec<&Stmt>>()),
+                );
+            }
+            s.push(')');
+            return s;
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_function_decl(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Function {
+            name,
+            parameters,
+            body,
+            ret,
+        } = stmt
+        {
+            let parameters = parameters
+                .iter()
+                .flatten()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>()
+                .join(" ");
+            let head = format!("fun {}({})", name, parameters);
+            return match ret {
+                Some(ret) => self.parenthesize(&format!("{} =>", head), &[ret]),
+                None => self.parenthesize_stmts(&head, &[body]),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_return(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Return(value) = stmt {
+            return match value {
+                Some(value) => self.parenthesize("return", &[value]),
+                None => "(return)".to_string(),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_import(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Import(path) = stmt {
+            return format!("(import {})", lox_escape(path));
+        }
+        panic!("{:?}", stmt)
+    }
+}
+
+// Prints the tree back as Lox that parses to the same tree. Groupings are printed as the
+// parentheses they came from, and any other operand that binds looser than its position allows
+// (as in trees that weren't parsed) is parenthesized as well.
+// The minimal printer ignores the source's groupings and only adds the parentheses precedence
+// requires, so its output re-parses to an equivalent tree rather than an identical one.
+pub struct SourcePrinter {
+    minimal: bool,
+}
+
+// Binding strength of each level of the grammar, loosest first. Negative number literals only
+// come from folding and print with a leading `-`, so they rank as unary.
+const ASSIGNMENT: u8 = 1;
+const COMPARISON: u8 = 6;
+const UNARY: u8 = 13;
+const POWER: u8 = 14;
+const PRIMARY: u8 = 15;
+
+// `++x` desugars to `x = x + 1`, which binds as loosely as any assignment, so it's recognized and
+// printed as written. `x += 1` and `x = x + 1` parse to the same tree and print this way too.
+fn prefix(expr: &Expr) -> Option<(&Token, &'static str)> {
+    if let Expr::Assign { name, value } = expr {
+        if let Expr::Binary {
+            left,
+            operator,
+            right,
+            ..
+        } = &**value
+        {
+            let prefix = match operator {
+                Operator::Plus => "++",
+                Operator::Minus => "--",
+                _ => return None,
+            };
+            if **right == Expr::L(Literal::Float(1.0))
+                && matches!(&**left, Expr::Variable { name: n, .. } if n == name)
+            {
+                return Some((name, prefix));
+            }
+        }
+    }
+    None
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    if prefix(expr).is_some() {
+        return UNARY;
+    }
+    match expr {
+        Expr::Assign { .. } | Expr::SetIndex { .. } => ASSIGNMENT,
+        Expr::Logical { operator, .. } | Expr::Binary { operator, .. } => match operator {
+            Operator::Or => 2,
+            Operator::Coalesce => 3,
+            Operator::And => 4,
+            Operator::Equal | Operator::NotEqual => 5,
+            Operator::GreaterThan
+            | Operator::GreaterThanOrEqual
+            | Operator::LessThan
+            | Operator::LessThanOrEqual => COMPARISON,
+            Operator::BitOr => 7,
+            Operator::BitXor => 8,
+            Operator::BitAnd => 9,
+            Operator::ShiftLeft | Operator::ShiftRight => 10,
+            Operator::Plus | Operator::Minus => 11,
+            Operator::Star | Operator::Slash => 12,
+            Operator::Power => POWER,
+            Operator::Not => UNARY,
+        },
+        Expr::Unary { .. } => UNARY,
+        Expr::L(Literal::Float(f)) if f.is_sign_negative() => UNARY,
+        _ => PRIMARY,
+    }
+}
+
+impl SourcePrinter {
+    pub fn new() -> Self {
+        SourcePrinter { minimal: false }
+    }
+
+    pub fn minimal() -> Self {
+        SourcePrinter { minimal: true }
+    }
+
+    pub fn print(&mut self, stmts: &[Stmt]) -> String {
+        stmts
+            .iter()
+            .map(|stmt| stmt.accept(self))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn print_expr(&mut self, expr: &Expr) -> String {
+        expr.accept(self)
+    }
+
+    // Prints `expr` where the grammar expects something binding at least as tightly as `min`.
+    fn operand(&mut self, mut expr: &Expr, min: u8) -> String {
+        while let (true, Expr::Grouping { expression }) = (self.minimal, expr) {
+            expr = expression;
+        }
+        let s = expr.accept(self);
+        if precedence(expr) < min {
+            format!("({})", s)
+        } else {
+            s
+        }
+    }
+
+    fn join(&mut self, exprs: &[Expr]) -> String {
+        exprs
+            .iter()
+            .map(|expr| expr.accept(self))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> String {
+        let mut s = "{".to_string();
+        for stmt in stmts {
+            s.push(' ');
+            s.push_str(&stmt.accept(self));
+        }
+        s.push_str(" }");
+        s
+    }
+
+    // One `name` or `name = value` of a var declaration, without the keyword.
+    fn binding(&mut self, stmt: &Stmt) -> String {
+        match stmt {
+            Stmt::Variable {
+                name,
+                initializer: Some(initializer),
+            } => format!("{} = {}", name, initializer.accept(self)),
+            Stmt::Variable { name, .. } => name.to_string(),
+            stmt => panic!("{:?}", stmt),
+        }
+    }
+}
+
+impl Default for SourcePrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExprVisitor for SourcePrinter {
+    type Result = String;
+
+    fn visit_literal(&mut self, expr: &Literal) -> Self::Result {
+        match expr {
+            Literal::String(s) => lox_escape(s),
+            l => l.to_string(),
+        }
+    }
+
+    fn visit_unary(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Unary {
+            operator, right, ..
+        } = expr
+        {
+            // `- -x` mustn't run together into the `--` token.
+            let right = self.operand(right, UNARY);
+            let space = if right.starts_with('-') { " " } else { "" };
+            return format!("{}{}{}", operator, space, right);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_binary(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Binary {
+            left,
+            operator,
+            right,
+            ..
+        } = expr
+        {
+            let p = precedence(expr);
+            let (left, right) = match operator {
+                Operator::Power => (self.operand(left, PRIMARY), self.operand(right, UNARY)),
+                _ if p == COMPARISON => (self.operand(left, p + 1), self.operand(right, p + 1)),
+                _ => (self.operand(left, p), self.operand(right, p + 1)),
+            };
+            return format!("{} {} {}", left, operator, right);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_logical(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Logical {
+            left,
+            operator,
+            right,
+        } = expr
+        {
+            let p = precedence(expr);
+            let (left, right) = (self.operand(left, p), self.operand(right, p + 1));
+            return format!("{} {} {}", left, operator, right);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_grouping(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Grouping { expression } = expr {
+            if self.minimal {
+                return expression.accept(self);
+            }
+            return format!("({})", expression.accept(self));
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_assign(&mut self, expr: &Expr) -> Self::Result {
+        if let Some((name, prefix)) = prefix(expr) {
+            return format!("{}{}", prefix, name);
+        }
+        if let Expr::Assign { name, value } = expr {
+            return format!("{} = {}", name, value.accept(self));
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_postfix(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Postfix { name, operator, .. } = expr {
+            return format!("{}{}{}", name, operator, operator);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_variable(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Variable { name, .. } = expr {
+            return name.to_string();
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_call(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Call {
+            callee, arguments, ..
+        } = expr
+        {
+            let callee = self.operand(callee, PRIMARY);
+            return format!("{}({})", callee, self.join(arguments));
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_list(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::List { elements } = expr {
+            return format!("[{}]", self.join(elements));
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_map(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Map { entries } = expr {
+            let entries = entries
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key.accept(self), value.accept(self)))
+                .collect::<Vec<String>>()
+                .join(", ");
+            return format!("{{{}}}", entries);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_index(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Index { object, index } = expr {
+            let object = self.operand(object, PRIMARY);
+            return format!("{}[{}]", object, index.accept(self));
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_set_index(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::SetIndex {
+            object,
+            index,
+            value,
+        } = expr
+        {
+            let object = self.operand(object, PRIMARY);
+            return format!(
+                "{}[{}] = {}",
+                object,
+                index.accept(self),
+                value.accept(self)
+            );
+        }
+        panic!("{:?}", expr)
+    }
+}
+
+impl StmtVisitor for SourcePrinter {
+    type Result = String;
+
+    fn visit_expr_stmt(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Expr(expr) = stmt {
+            return format!("{};", expr.accept(self));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_print(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Print(exprs) = stmt {
+            let exprs: Vec<String> = exprs.iter().map(|e| e.accept(self)).collect();
+            return format!("print {};", exprs.join(", "));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_var_decl(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Variable { .. } = stmt {
+            return format!("var {};", self.binding(stmt));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_var_list(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::VarList(stmts) = stmt {
+            let declarations = stmts
+                .iter()
+                .map(|stmt| self.binding(stmt))
+                .collect::<Vec<String>>()
+                .join(", ");
+            return format!("var {};", declarations);
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_const(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Const { name, initializer } = stmt {
+            return format!("const {} = {};", name, initializer.accept(self));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_block(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Block(stmts) = stmt {
+            return self.block(stmts);
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_if(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } = stmt
+        {
+            let mut s = format!(
+                "if ({}) {}",
+                condition.accept(self),
+                then_branch.accept(self)
+            );
+            if let Some(else_branch) = else_branch {
+                s.push_str(&format!(" else {}", else_branch.accept(self)));
+            }
+            return s;
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_while(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::While { condition, body } = stmt {
+            return format!("while ({}) {}", condition.accept(self), body.accept(self));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_switch(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Switch {
+            subject,
+            cases,
+            default,
+        } = stmt
+        {
+            let mut s = format!("switch ({}) {{", subject.accept(self));
+            for (value, body) in cases {
+                s.push_str(&format!(" case {}:", value.accept(self)));
+                for stmt in body {
+                    s.push(' ');
+                    s.push_str(&stmt.accept(self));
+                }
+            }
+            if let Some(body) = default {
+                s.push_str(" default:");
+                for stmt in body {
+                    s.push(' ');
+                    s.push_str(&stmt.accept(self));
+                }
+            }
+            s.push_str(" }");
+            return s;
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_function_decl(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Function {
+            name,
+            parameters,
+            body,
+            ret,
+        } = stmt
+        {
+            let parameters = parameters
+                .iter()
+                .flatten()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            return match ret {
+                Some(ret) => format!("fun {}({}) => {};", name, parameters, ret.accept(self)),
+                None => format!("fun {}({}) {}", name, parameters, body.accept(self)),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_return(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Return(value) = stmt {
+            return match value {
+                Some(value) => format!("return {};", value.accept(self)),
+                None => "return;".to_string(),
+            };
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_import(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Import(path) = stmt {
+            return format!("import {};", lox_escape(path));
+        }
+        panic!("{:?}", stmt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::{lex, Token};
+    use crate::parser::ast::Stmt;
+    use crate::parser::parser::Parser;
+    use crate::parser::printer::{AstPrinter, SourcePrinter};
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let input: Vec<char> = source.chars().collect();
+        let tokens = lex(&input).unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    fn print(source: &str) -> String {
+        AstPrinter.print(&parse(source))
+    }
+
+    #[test]
+    fn print_expressions() {
+        assert_eq!(print("-1 + (2 * 3);"), "(; (+ (- 1) (group (* 2 3))))");
+        assert_eq!(print("a = b or c;"), "(; (= a (or b c)))");
+        assert_eq!(
+            print(r#"f(1, "s")[0] = {"k": [nil]};"#),
+            r#"(; (= (index (call f 1 "s") 0) (map ("k" (list nil)))))"#
+        );
+    }
+
+    #[test]
+    fn print_escapes() {
+        let source = r#"print "say \"hi\"\nbye\t\\ \u{1}\u{e9}";"#;
+        let printed = print(source);
+        assert_eq!(printed, r#"(print "say \"hi\"\nbye\t\\ \u{1}é")"#);
+        // The printed literal lexes back to the string it came from.
+        let literal: Vec<char> = printed["(print ".len()..printed.len() - 1]
+            .chars()
+            .collect();
+        assert_eq!(
+            lex(&literal).unwrap(),
+            vec![
+                Token::String("say \"hi\"\nbye\t\\ \u{1}é".to_string()),
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn print_program() {
+        let source = r#"
+            var a = 1;
+            if (a < 2) {
+                print "small";
+            } else print "big";
+            fun add(x, y) {
+                return x + y;
+            }
+            fun sq(x) => x * x;
+            while (true) return;
+            import "lib.lox";
+        "#;
+        assert_eq!(
+            print(source),
+            [
+                "(var a = 1)",
+                r#"(if (< a 2) (block (print "small")) (print "big"))"#,
+                "(fun add(x y) (block (return (+ x y))))",
+                "(fun sq(x) => (* x x))",
+                "(while true (return))",
+                r#"(import "lib.lox")"#,
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn print_source() {
+        let source = |s: &str| SourcePrinter::new().print(&parse(s));
+        assert_eq!(source("x = -(1+2)*f(a)[0];"), "x = -(1 + 2) * f(a)[0];");
+        assert_eq!(source("print - -1; i++;"), "print - -1;\ni++;");
+        assert_eq!(source("print ++a * 2, - --b;"), "print ++a * 2, - --b;");
+        assert_eq!(
+            source("import \"a\\tb\u{ad}.lox\";"),
+            "import \"a\\tb\u{ad}.lox\";"
+        );
+        assert_eq!(
+            source("fun f(a,b) { if (a) return b; else return; }"),
+            "fun f(a, b) { if (a) return b; else return; }"
+        );
+    }
+
+    #[test]
+    fn print_minimal() {
+        let minimal = |s: &str| SourcePrinter::minimal().print(&parse(s));
+        assert_eq!(minimal("1 + 2 * 3;"), "1 + 2 * 3;");
+        assert_eq!(minimal("(1 + 2) * 3;"), "(1 + 2) * 3;");
+        assert_eq!(minimal("((1)) + (2 * 3);"), "1 + 2 * 3;");
+        assert_eq!(minimal("(1 - 2) - 3;"), "1 - 2 - 3;");
+        assert_eq!(minimal("1 - (2 - 3);"), "1 - (2 - 3);");
+        assert_eq!(minimal("(-2) ** 2; -(2 ** 2);"), "(-2) ** 2;\n-2 ** 2;");
+        assert_eq!(
+            minimal("2 ** (3 ** 2); (2 ** 3) ** 2;"),
+            "2 ** 3 ** 2;\n(2 ** 3) ** 2;"
+        );
+        assert_eq!(minimal("(a < b) == (c and d);"), "a < b == (c and d);");
+        assert_eq!(minimal("x = (y = 1);"), "x = y = 1;");
+        assert_eq!(minimal("(f)(1)[(2)];"), "f(1)[2];");
+
+        // The minimal form is a fixed point: printing its parse gives it back.
+        for source in &[
+            "(1 + 2) * -(3 - (4 ** (5)));",
+            "(a ?? b) or (c and (d or e));",
+        ] {
+            let printed = minimal(source);
+            assert_eq!(minimal(&printed), printed);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for source in &[
+            "-1 + (2 * 3) - - -4;",
+            "a = b or c and !d ?? e;",
+            "x = (1 + 2) * 3 ** -2 ** 2 / (4 - (5 - 6));",
+            r#"f(1, "s\t\"q\"")[0] = {"k": [nil, true], 2: {}};"#,
+            r#"print "line\nbreak\r\0\u{7f}\\";"#,
+            "a += 1; b++; --c; d--; print e++ * 2; x = 1 << 2 | 3 & 4 ^ 5 >> 1;",
+            "print ++a * 2; print -++a, - --a, 1 - --a;",
+            "var a = 1, b; var c; print (a == b) != (1 < 2);",
+            "const k = 1 + 2;",
+            r#"print 1, "two", f(a, b);"#,
+            r#"
+                if (a < 2) { print "small"; } else if (a > 3) print "big"; else {}
+                fun add(x, y) { return x + y; }
+                fun sq(x) => x * x;
+                for (var i = 0; i < 10; i = i + 1) print i;
+                while (true) return;
+                switch (a) { case 1: print 1; case 2: default: print 3; }
+                import "lib.lox";
+            "#,
+            r#"import "dir\tname \"q\"\u{1}.lox";"#,
+        ] {
+            let stmts = parse(source);
+            let printed = SourcePrinter::new().print(&stmts);
+            assert_eq!(parse(&printed), stmts, "{}", printed);
+        }
+    }
+}
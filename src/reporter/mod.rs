@@ -0,0 +1,105 @@
+use crate::interpreter::error::RuntimeError;
+use crate::parser::error::ParseError;
+
+// Quotes the source line an error points at, rustc style, with a caret under the column when it's
+// known. Errors without a line, or whose line isn't in the source, are reported as they are.
+pub struct ErrorReporter<'a> {
+    lines: Vec<&'a str>,
+}
+
+impl<'a> ErrorReporter<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            lines: source.lines().collect(),
+        }
+    }
+
+    pub fn parse_error(&self, error: &ParseError) -> String {
+        self.format(&error.to_string(), error.line, error.column)
+    }
+
+    pub fn runtime_error(&self, error: &RuntimeError) -> String {
+        self.format(&error.to_string(), error.line, error.column)
+    }
+
+    fn format(&self, message: &str, line: Option<usize>, column: Option<usize>) -> String {
+        let text = match line.and_then(|line| self.lines.get(line.checked_sub(1)?)) {
+            Some(text) => text,
+            None => return message.to_string(),
+        };
+        let number = line.unwrap().to_string();
+        let gutter = " ".repeat(number.len());
+        let mut s = format!("{}\n{} |\n{} | {}", message, gutter, number, text);
+        if let Some(column) = column {
+            // Tabs are kept so the caret lines up however wide the terminal draws them.
+            let indent: String = text
+                .chars()
+                .take(column.saturating_sub(1))
+                .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+                .collect();
+            s.push_str(&format!("\n{} | {}^", gutter, indent));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::error::RuntimeError;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::lex_with_positions;
+    use crate::parser::error::ParseError;
+    use crate::parser::parser::Parser;
+    use crate::reporter::ErrorReporter;
+
+    #[test]
+    fn caret() {
+        let reporter = ErrorReporter::new("print 1;\nvar = 1;\n");
+        let error = ParseError::new("Expect variable name, found Assign")
+            .at(Some(2))
+            .at_column(Some(5));
+        assert_eq!(
+            reporter.parse_error(&error),
+            "[line 2] Expect variable name, found Assign\n  |\n2 | var = 1;\n  |     ^"
+        );
+    }
+
+    #[test]
+    fn tabs_and_wide_gutter() {
+        let source = format!("{}\tx = ;", "\n".repeat(9));
+        let reporter = ErrorReporter::new(&source);
+        let error = ParseError::new("Oops").at(Some(10)).at_column(Some(6));
+        assert_eq!(
+            reporter.parse_error(&error),
+            "[line 10] Oops\n   |\n10 | \tx = ;\n   | \t    ^"
+        );
+    }
+
+    #[test]
+    fn runtime_caret() {
+        let source = "var a = 1;\nprint a + nil;";
+        let input: Vec<char> = source.chars().collect();
+        let (tokens, positions) = lex_with_positions(&input).unwrap();
+        let stmts = Parser::with_positions(tokens, positions).parse().unwrap();
+        let error = Interpreter::new().interpret(&stmts).unwrap_err();
+        assert_eq!(
+            ErrorReporter::new(source).runtime_error(&error),
+            "[line 2] Operands must be two numbers or two strings.\n  |\n2 | print a + nil;\n  |         ^"
+        );
+    }
+
+    #[test]
+    fn without_position() {
+        let reporter = ErrorReporter::new("print a;");
+        let error = RuntimeError::new("Undefined variable 'a'.");
+        assert_eq!(reporter.runtime_error(&error), "Undefined variable 'a'.");
+        assert_eq!(
+            reporter.runtime_error(&error.clone().at(Some(1))),
+            "[line 1] Undefined variable 'a'.\n  |\n1 | print a;"
+        );
+        assert_eq!(
+            reporter.runtime_error(&error.at(Some(7))),
+            "[line 7] Undefined variable 'a'."
+        );
+    }
+}
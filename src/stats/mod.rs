@@ -0,0 +1,111 @@
+use crate::parser::ast::{Expr, Stmt};
+
+// Every statement and expression is one node, literals included. Names and parameter lists are
+// part of their declaration and don't count separately.
+pub fn count_nodes(stmts: &[Stmt]) -> usize {
+    stmts.iter().map(count_stmt).sum()
+}
+
+fn count_stmt(stmt: &Stmt) -> usize {
+    1 + match stmt {
+        Stmt::Expr(expr) => count_expr(expr),
+        Stmt::Print(exprs) => count_exprs(exprs),
+        Stmt::Variable { initializer, .. } => initializer.as_deref().map_or(0, count_expr),
+        Stmt::VarList(stmts) | Stmt::Block(stmts) => count_nodes(stmts),
+        Stmt::Const { initializer, .. } => count_expr(initializer),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            count_expr(condition)
+                + count_stmt(then_branch)
+                + else_branch.as_deref().map_or(0, count_stmt)
+        }
+        Stmt::While { condition, body } => count_expr(condition) + count_stmt(body),
+        Stmt::Switch {
+            subject,
+            cases,
+            default,
+        } => {
+            count_expr(subject)
+                + cases
+                    .iter()
+                    .map(|(value, body)| count_expr(value) + count_nodes(body))
+                    .sum::<usize>()
+                + default.as_deref().map_or(0, count_nodes)
+        }
+        Stmt::Function { body, ret, .. } => count_stmt(body) + ret.as_deref().map_or(0, count_expr),
+        Stmt::Return(value) => value.as_deref().map_or(0, count_expr),
+        Stmt::Import(_) => 0,
+    }
+}
+
+fn count_exprs(exprs: &[Expr]) -> usize {
+    exprs.iter().map(count_expr).sum()
+}
+
+fn count_expr(expr: &Expr) -> usize {
+    1 + match expr {
+        Expr::L(_) | Expr::Variable { .. } | Expr::Postfix { .. } => 0,
+        Expr::Unary { right, .. } => count_expr(right),
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            count_expr(left) + count_expr(right)
+        }
+        Expr::Grouping { expression } => count_expr(expression),
+        Expr::Assign { value, .. } => count_expr(value),
+        Expr::Call {
+            callee, arguments, ..
+        } => count_expr(callee) + count_exprs(arguments),
+        Expr::List { elements } => count_exprs(elements),
+        Expr::Map { entries } => entries
+            .iter()
+            .map(|(k, v)| count_expr(k) + count_expr(v))
+            .sum(),
+        Expr::Index { object, index } => count_expr(object) + count_expr(index),
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+        } => count_expr(object) + count_expr(index) + count_expr(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::lex;
+    use crate::optimizer::Optimizer;
+    use crate::parser::ast::Stmt;
+    use crate::parser::parser::Parser;
+    use crate::stats::count_nodes;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let input: Vec<char> = source.chars().collect();
+        let tokens = lex(&input).unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    #[test]
+    fn count() {
+        assert_eq!(count_nodes(&[]), 0);
+        // var(1 + 2) is 4 nodes, print a is 2.
+        assert_eq!(count_nodes(&parse("var a = 1 + 2; print a;")), 6);
+        // The function, its block, the return and a * a, then the call statement, call, callee and
+        // argument.
+        assert_eq!(
+            count_nodes(&parse("fun sq(a) { return a * a; } sq(3);")),
+            10
+        );
+        assert_eq!(
+            count_nodes(&parse("if (x) { m[0] = [1, 2]; } else print {1: nil};")),
+            14
+        );
+    }
+
+    #[test]
+    fn optimizer_shrinks() {
+        let stmts = parse("print (2 + 3) * 4 - -1;");
+        assert_eq!(count_nodes(&stmts), 10);
+        assert_eq!(count_nodes(&Optimizer.optimize(&stmts)), 2);
+    }
+}
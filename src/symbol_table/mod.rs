@@ -1,120 +1,329 @@
+use crate::intern::Symbol;
 use crate::interpreter::callable::Callable;
-use crate::lexer;
+use crate::interpreter::error::RuntimeError;
 use crate::parser::ast::Literal;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
+use crate::sync::{Rc, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Clone)]
 pub enum Object {
     L(Literal),
-    C(Rc<dyn Callable<Result = Object>>),
+    C(Rc<dyn Callable>),
+    List(Rc<RefCell<Vec<Object>>>),
+    Map(Rc<RefCell<HashMap<Object, Object>>>),
 }
 
+// Numbers compare with IEEE semantics, so `0 == -0` is true and NaN is unequal to everything,
+// itself included. Values of different types are never equal and lists, maps and callables
+// compare by identity.
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::L(left), Object::L(right)) => left == right,
+            (Object::C(left), Object::C(right)) => Rc::ptr_eq(left, right),
+            (Object::List(left), Object::List(right)) => Rc::ptr_eq(left, right),
+            (Object::Map(left), Object::Map(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+// The user-facing form: strings aren't quoted and map entries are sorted so output is stable.
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.display(&mut Vec::new()))
+    }
+}
+
+impl Object {
+    // `seen` holds the lists and maps being printed further out, so a container that holds itself
+    // prints as `[...]` or `{...}` where it recurs instead of overflowing the stack.
+    fn display(&self, seen: &mut Vec<usize>) -> String {
+        match self {
+            Object::L(l) => l.to_string(),
+            Object::C(c) => c.to_string(),
+            Object::List(l) => {
+                let id = Rc::as_ptr(l) as usize;
+                if seen.contains(&id) {
+                    return "[...]".to_string();
+                }
+                seen.push(id);
+                let elements: Vec<String> = l.borrow().iter().map(|o| o.display(seen)).collect();
+                seen.pop();
+                format!("[{}]", elements.join(", "))
+            }
+            Object::Map(m) => {
+                let id = Rc::as_ptr(m) as usize;
+                if seen.contains(&id) {
+                    return "{...}".to_string();
+                }
+                seen.push(id);
+                let mut entries: Vec<String> = m
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.display(seen), v.display(seen)))
+                    .collect();
+                seen.pop();
+                entries.sort();
+                format!("{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+// NaN breaks the reflexivity Eq promises, so the interpreter refuses NaN map keys. Numbers hash
+// their bits with -0 folded into 0 to stay consistent with ==.
+impl Eq for Literal {}
+
+impl Hash for Literal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Literal::Float(f) => (if *f == 0.0 { 0.0 } else { *f }).to_bits().hash(state),
+            Literal::Bool(b) => b.hash(state),
+            Literal::String(s) => s.hash(state),
+            Literal::Nil(_) => {}
+        }
+    }
+}
+
+impl Eq for Object {}
+
+impl Hash for Object {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Object::L(l) => l.hash(state),
+            Object::C(c) => (Rc::as_ptr(c) as *const ()).hash(state),
+            Object::List(l) => Rc::as_ptr(l).hash(state),
+            Object::Map(m) => Rc::as_ptr(m).hash(state),
+        }
+    }
+}
+
+// Scopes link to their parent, so assigning through a child updates the defining scope wherever
+// it lives. A clone shares its values, constants and enclosing chain with the original.
 #[derive(Debug, Clone, Default)]
 pub struct SymbolTable {
     pub enclosing: Option<Rc<RefCell<SymbolTable>>>,
-    pub values: Rc<RefCell<HashMap<String, Object>>>,
+    pub values: Rc<RefCell<HashMap<Symbol, Object>>>,
+    pub constants: Rc<RefCell<HashSet<Symbol>>>,
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
-        return Self {
+        Self {
             enclosing: None,
             values: Rc::new(RefCell::new(Default::default())),
-        };
+            constants: Rc::new(RefCell::new(Default::default())),
+        }
+    }
+
+    // Redeclaring a name with `var` in the same scope makes a fresh, assignable binding.
+    pub fn define(&mut self, name: impl Into<Symbol>, value: Object) {
+        let name = name.into();
+        self.constants.borrow_mut().remove(&name);
+        self.values.borrow_mut().insert(name, value);
     }
 
-    pub fn define(&mut self, name: &str, value: Object) {
-        self.values.borrow_mut().insert(name.to_owned(), value);
+    pub fn define_const(&mut self, name: impl Into<Symbol>, value: Object) {
+        let name = name.into();
+        self.values.borrow_mut().insert(name, value);
+        self.constants.borrow_mut().insert(name);
     }
 
-    pub fn exists(&mut self, name: &str) -> bool {
-        self.values.borrow().contains_key(name)
+    pub fn exists(&mut self, name: impl Into<Symbol>) -> bool {
+        self.values.borrow().contains_key(&name.into())
     }
 
-    pub fn get(&self, name: &str) -> Object {
-        if self.values.borrow().contains_key(name) {
-            self.values.borrow().get(name).unwrap().clone()
+    pub fn get(&self, name: impl Into<Symbol>) -> Result<Object, RuntimeError> {
+        let name = name.into();
+        if let Some(value) = self.values.borrow().get(&name) {
+            Ok(value.clone())
         } else if let Some(ref env) = self.enclosing {
             env.borrow().get(name)
         } else {
-            panic!("{:?} {:#?}", name, self)
+            Err(RuntimeError::new(&format!(
+                "Undefined variable '{}'.",
+                name
+            )))
         }
     }
 
-    pub fn assign(&mut self, name: &str, value: Object) {
-        if self.values.borrow().contains_key(name) {
-            self.values.borrow_mut().insert(name.to_owned(), value);
+    pub fn assign(&mut self, name: impl Into<Symbol>, value: Object) -> Result<(), RuntimeError> {
+        let name = name.into();
+        if self.values.borrow().contains_key(&name) {
+            if self.constants.borrow().contains(&name) {
+                return Err(RuntimeError::new(&format!(
+                    "Cannot assign to const '{}'.",
+                    name
+                )));
+            }
+            self.values.borrow_mut().insert(name, value);
+            Ok(())
         } else if let Some(ref mut env) = self.enclosing {
             env.borrow_mut().assign(name, value)
         } else {
-            panic!()
-        }
-    }
-
-    pub fn deep_copy(&self) -> Self {
-        let mut values = HashMap::new();
-        for (name, object) in self.values.borrow().iter() {
-            values.insert(name.to_owned(), object.clone());
-        }
-        SymbolTable {
-            enclosing: self.enclosing.clone(),
-            values: Rc::new(RefCell::new(values)),
+            Err(RuntimeError::new(&format!(
+                "Undefined variable '{}'.",
+                name
+            )))
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::lexer::Token;
     use crate::parser::ast::Literal;
     use crate::symbol_table::{Object, SymbolTable};
-    use std::cell::RefCell;
-    use std::rc::Rc;
+    use crate::sync::{Rc, RefCell};
+    use std::collections::HashMap;
 
     #[test]
     fn test() {
         let mut e = SymbolTable {
             values: Default::default(),
             enclosing: None,
+            constants: Default::default(),
         };
         e.define("bob", Object::L(Literal::Float(1.0)));
         println!("{:?}", e.get("bob"));
     }
 
+    #[test]
+    fn undefined() {
+        let mut outer = SymbolTable::new();
+        outer.define("x", Object::L(Literal::Float(1.0)));
+        let inner = SymbolTable {
+            values: Default::default(),
+            enclosing: Some(Rc::new(RefCell::new(outer))),
+            constants: Default::default(),
+        };
+        assert!(inner.get("x").is_ok());
+        assert_eq!(
+            inner.get("y").unwrap_err().message,
+            "Undefined variable 'y'."
+        );
+    }
+
+    #[test]
+    fn child_writes_reach_enclosing() {
+        let outer = Rc::new(RefCell::new(SymbolTable::new()));
+        outer
+            .borrow_mut()
+            .define("x", Object::L(Literal::Float(1.0)));
+        let mut child = SymbolTable {
+            values: Default::default(),
+            enclosing: Some(outer.clone()),
+            constants: Default::default(),
+        };
+        child.assign("x", Object::L(Literal::Float(2.0))).unwrap();
+        child.define("y", Object::L(Literal::Float(3.0)));
+        assert!(matches!(
+            outer.borrow().get("x"),
+            Ok(Object::L(Literal::Float(f))) if f == 2.0
+        ));
+        assert!(outer.borrow().get("y").is_err());
+
+        // A define in the child shadows without touching the enclosing scope.
+        child.define("x", Object::L(Literal::Float(4.0)));
+        child.assign("x", Object::L(Literal::Float(5.0))).unwrap();
+        assert!(matches!(
+            outer.borrow().get("x"),
+            Ok(Object::L(Literal::Float(f))) if f == 2.0
+        ));
+    }
+
+    #[test]
+    fn clone_shares_values() {
+        let mut original = SymbolTable::new();
+        original.define("x", Object::L(Literal::Float(1.0)));
+        let mut copy = original.clone();
+        copy.assign("x", Object::L(Literal::Float(2.0))).unwrap();
+        copy.define("y", Object::L(Literal::Float(3.0)));
+        assert!(matches!(
+            original.get("x"),
+            Ok(Object::L(Literal::Float(f))) if f == 2.0
+        ));
+        assert!(original.get("y").is_ok());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(format!("{}", Object::L(Literal::Float(3.0))), "3");
+        assert_eq!(format!("{}", Object::L(Literal::Float(0.5))), "0.5");
+        assert_eq!(
+            format!("{}", Object::L(Literal::String("a b".to_string().into()))),
+            "a b"
+        );
+        assert_eq!(format!("{}", Object::L(Literal::Nil(()))), "nil");
+        let list = Object::List(Rc::new(RefCell::new(vec![
+            Object::L(Literal::Bool(true)),
+            Object::L(Literal::String("s".to_string().into())),
+        ])));
+        assert_eq!(format!("{}", list), "[true, s]");
+    }
+
+    #[test]
+    fn display_cycle() {
+        let list = Rc::new(RefCell::new(vec![Object::L(Literal::Float(1.0))]));
+        list.borrow_mut().push(Object::List(list.clone()));
+        let map = Rc::new(RefCell::new(HashMap::new()));
+        map.borrow_mut()
+            .insert(Object::L(Literal::Float(1.0)), Object::Map(map.clone()));
+        map.borrow_mut()
+            .insert(Object::L(Literal::Float(2.0)), Object::List(list.clone()));
+        assert_eq!(Object::List(list.clone()).to_string(), "[1, [...]]");
+        assert_eq!(
+            Object::Map(map.clone()).to_string(),
+            "{1: {...}, 2: [1, [...]]}"
+        );
+
+        // The same list twice side by side isn't a cycle.
+        let empty = Rc::new(RefCell::new(Vec::new()));
+        let pair = Object::List(Rc::new(RefCell::new(vec![
+            Object::List(empty.clone()),
+            Object::List(empty),
+        ])));
+        assert_eq!(pair.to_string(), "[[], []]");
+
+        list.borrow_mut().clear();
+        map.borrow_mut().clear();
+    }
+
     #[test]
     fn env() {
-        let mut e = SymbolTable {
+        let e = SymbolTable {
             values: Default::default(),
             enclosing: None,
+            constants: Default::default(),
         };
         println!("{:?}", e);
     }
     #[test]
     fn inner() {
-        let mut outer = Rc::new(RefCell::new(SymbolTable {
+        let outer = Rc::new(RefCell::new(SymbolTable {
             values: Default::default(),
             enclosing: None,
+            constants: Default::default(),
         }));
 
         outer
             .borrow_mut()
-            .define("x", Object::L(Literal::String("inner".to_string())));
+            .define("x", Object::L(Literal::String("inner".to_string().into())));
         println!("{:#?}", outer);
 
         let mut inner = SymbolTable {
             values: Default::default(),
             enclosing: Some(outer.clone()),
+            constants: Default::default(),
         };
         println!("{:#?}", inner);
-        inner.assign("x", Object::L(Literal::String("outer".to_string())));
-        println!("{:#?}", inner);
-        println!("{:#?}", outer);
-
-        let mut copy = inner.deep_copy();
-        copy.assign("x", Object::L(Literal::String("copy".to_string())));
-        println!("{:#?}", copy);
+        inner
+            .assign("x", Object::L(Literal::String("outer".to_string().into())))
+            .unwrap();
         println!("{:#?}", inner);
         println!("{:#?}", outer);
     }
@@ -0,0 +1,46 @@
+// With the `sync` feature, values shared between scopes and closures live behind Arc and RwLock
+// so that an Interpreter can be moved to another thread. The names stay Rc and RefCell so call
+// sites read the same either way; `borrow` takes a read lock and `borrow_mut` a write lock. A
+// panic caught by the REPL can poison a lock, which is ignored since the REPL recovers anyway.
+#[cfg(not(feature = "sync"))]
+pub use std::cell::RefCell;
+#[cfg(not(feature = "sync"))]
+pub use std::rc::Rc;
+
+#[cfg(feature = "sync")]
+pub use std::sync::Arc as Rc;
+
+#[cfg(feature = "sync")]
+#[derive(Debug, Default)]
+pub struct RefCell<T>(std::sync::RwLock<T>);
+
+#[cfg(feature = "sync")]
+impl<T> RefCell<T> {
+    pub fn new(value: T) -> Self {
+        RefCell(std::sync::RwLock::new(value))
+    }
+
+    pub fn borrow(&self) -> std::sync::RwLockReadGuard<'_, T> {
+        self.0
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub fn borrow_mut(&self) -> std::sync::RwLockWriteGuard<'_, T> {
+        self.0
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+// Send + Sync with the `sync` feature and nothing without it, for trait objects that end up
+// inside shared values.
+#[cfg(feature = "sync")]
+pub trait MaybeSend: Send + Sync {}
+#[cfg(feature = "sync")]
+impl<T: Send + Sync + ?Sized> MaybeSend for T {}
+
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSend for T {}
@@ -0,0 +1,48 @@
+use crate::symbol_table::Object;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OpCode {
+    Constant(usize),
+    Nil,
+    True,
+    False,
+    Pop,
+    DefineGlobal(usize),
+    GetGlobal(usize),
+    SetGlobal(usize),
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    // Pops this many values and prints them space-separated, first pushed first.
+    Print(usize),
+    Return,
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<OpCode>,
+    pub constants: Vec<Object>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, op: OpCode) {
+        self.code.push(op);
+    }
+
+    pub fn add_constant(&mut self, value: Object) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+}
@@ -0,0 +1,245 @@
+use crate::lexer;
+use crate::lexer::Operator;
+use crate::parser::ast::{Expr, ExprData, ExprVisitor, Literal, Stmt, StmtData, StmtVisitor};
+use crate::symbol_table::Object;
+use crate::vm::chunk::{Chunk, OpCode};
+use crate::vm::error::CompileError;
+
+pub struct Compiler {
+    chunk: Chunk,
+}
+
+impl Compiler {
+    pub fn compile(stmts: &[Stmt]) -> Result<Chunk, CompileError> {
+        let mut compiler = Compiler {
+            chunk: Chunk::new(),
+        };
+        for stmt in stmts {
+            stmt.accept(&mut compiler)?;
+        }
+        compiler.chunk.write(OpCode::Return);
+        Ok(compiler.chunk)
+    }
+
+    fn identifier(&mut self, name: &lexer::Token) -> usize {
+        self.chunk
+            .add_constant(Object::L(Literal::String(name.to_string().into())))
+    }
+
+    fn unsupported(&self, what: &str) -> Result<(), CompileError> {
+        Err(CompileError::new(&format!(
+            "The bytecode compiler doesn't support {} yet.",
+            what
+        )))
+    }
+}
+
+impl ExprVisitor for Compiler {
+    type Result = Result<(), CompileError>;
+
+    fn visit_literal(&mut self, expr: &Literal) -> Self::Result {
+        match expr {
+            Literal::Nil(_) => self.chunk.write(OpCode::Nil),
+            Literal::Bool(true) => self.chunk.write(OpCode::True),
+            Literal::Bool(false) => self.chunk.write(OpCode::False),
+            l => {
+                let constant = self.chunk.add_constant(Object::L(l.clone()));
+                self.chunk.write(OpCode::Constant(constant))
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_unary(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Unary {
+            operator, right, ..
+        } = expr
+        {
+            right.accept(self)?;
+            let op = match operator {
+                Operator::Minus => OpCode::Negate,
+                Operator::Not => OpCode::Not,
+                _ => return self.unsupported(&format!("unary '{}'", operator)),
+            };
+            self.chunk.write(op);
+            return Ok(());
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_binary(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Binary {
+            left,
+            operator,
+            right,
+            ..
+        } = expr
+        {
+            left.accept(self)?;
+            right.accept(self)?;
+            let ops: &[OpCode] = match operator {
+                Operator::Plus => &[OpCode::Add],
+                Operator::Minus => &[OpCode::Subtract],
+                Operator::Star => &[OpCode::Multiply],
+                Operator::Slash => &[OpCode::Divide],
+                Operator::Equal => &[OpCode::Equal],
+                Operator::NotEqual => &[OpCode::Equal, OpCode::Not],
+                Operator::GreaterThan => &[OpCode::Greater],
+                Operator::GreaterThanOrEqual => &[OpCode::GreaterEqual],
+                Operator::LessThan => &[OpCode::Less],
+                Operator::LessThanOrEqual => &[OpCode::LessEqual],
+                _ => return self.unsupported(&format!("binary '{}'", operator)),
+            };
+            for op in ops {
+                self.chunk.write(*op);
+            }
+            return Ok(());
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_logical(&mut self, _expr: &Expr) -> Self::Result {
+        self.unsupported("logical operators")
+    }
+
+    fn visit_grouping(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Grouping { expression } = expr {
+            return expression.accept(self);
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_assign(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Assign { name, value } = expr {
+            value.accept(self)?;
+            let name = self.identifier(name);
+            self.chunk.write(OpCode::SetGlobal(name));
+            return Ok(());
+        }
+        panic!("{:?}", expr)
+    }
+
+    // Leaves the old value on the stack under the new one, which is stored and then popped.
+    fn visit_postfix(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Postfix { name, operator, .. } = expr {
+            let name = self.identifier(name);
+            let one = self.chunk.add_constant(Object::L(Literal::Float(1.0)));
+            self.chunk.write(OpCode::GetGlobal(name));
+            self.chunk.write(OpCode::GetGlobal(name));
+            self.chunk.write(OpCode::Constant(one));
+            self.chunk.write(match operator {
+                Operator::Plus => OpCode::Add,
+                _ => OpCode::Subtract,
+            });
+            self.chunk.write(OpCode::SetGlobal(name));
+            self.chunk.write(OpCode::Pop);
+            return Ok(());
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_variable(&mut self, expr: &Expr) -> Self::Result {
+        if let Expr::Variable { name, .. } = expr {
+            let name = self.identifier(name);
+            self.chunk.write(OpCode::GetGlobal(name));
+            return Ok(());
+        }
+        panic!("{:?}", expr)
+    }
+
+    fn visit_call(&mut self, _expr: &Expr) -> Self::Result {
+        self.unsupported("calls")
+    }
+
+    fn visit_list(&mut self, _expr: &Expr) -> Self::Result {
+        self.unsupported("lists")
+    }
+
+    fn visit_map(&mut self, _expr: &Expr) -> Self::Result {
+        self.unsupported("maps")
+    }
+
+    fn visit_index(&mut self, _expr: &Expr) -> Self::Result {
+        self.unsupported("indexing")
+    }
+
+    fn visit_set_index(&mut self, _expr: &Expr) -> Self::Result {
+        self.unsupported("indexing")
+    }
+}
+
+impl StmtVisitor for Compiler {
+    type Result = Result<(), CompileError>;
+
+    fn visit_expr_stmt(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Expr(expr) = stmt {
+            expr.accept(self)?;
+            self.chunk.write(OpCode::Pop);
+            return Ok(());
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_print(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Print(exprs) = stmt {
+            for expr in exprs {
+                expr.accept(self)?;
+            }
+            self.chunk.write(OpCode::Print(exprs.len()));
+            return Ok(());
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_var_decl(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::Variable { name, initializer } = stmt {
+            match initializer {
+                Some(initializer) => initializer.accept(self)?,
+                None => self.chunk.write(OpCode::Nil),
+            }
+            let name = self.identifier(name);
+            self.chunk.write(OpCode::DefineGlobal(name));
+            return Ok(());
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_var_list(&mut self, stmt: &Stmt) -> Self::Result {
+        if let Stmt::VarList(stmts) = stmt {
+            return stmts.iter().try_for_each(|stmt| stmt.accept(self));
+        }
+        panic!("{:?}", stmt)
+    }
+
+    fn visit_const(&mut self, _stmt: &Stmt) -> Self::Result {
+        self.unsupported("const declarations")
+    }
+
+    fn visit_block(&mut self, _stmt: &Stmt) -> Self::Result {
+        self.unsupported("blocks")
+    }
+
+    fn visit_if(&mut self, _stmt: &Stmt) -> Self::Result {
+        self.unsupported("if statements")
+    }
+
+    fn visit_while(&mut self, _stmt: &Stmt) -> Self::Result {
+        self.unsupported("loops")
+    }
+
+    fn visit_switch(&mut self, _stmt: &Stmt) -> Self::Result {
+        self.unsupported("switch statements")
+    }
+
+    fn visit_function_decl(&mut self, _stmt: &Stmt) -> Self::Result {
+        self.unsupported("functions")
+    }
+
+    fn visit_return(&mut self, _stmt: &Stmt) -> Self::Result {
+        self.unsupported("return statements")
+    }
+
+    fn visit_import(&mut self, _stmt: &Stmt) -> Self::Result {
+        self.unsupported("imports")
+    }
+}
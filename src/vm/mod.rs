@@ -0,0 +1,278 @@
+use crate::interpreter::error::RuntimeError;
+use crate::interpreter::{is_truthy, stringify};
+use crate::parser::ast::Literal;
+use crate::symbol_table::Object;
+use crate::vm::chunk::{Chunk, OpCode};
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+
+pub mod chunk;
+pub mod compiler;
+pub mod error;
+
+pub struct VM {
+    stack: Vec<Object>,
+    globals: HashMap<String, Object>,
+    output: Box<dyn Write>,
+}
+
+impl VM {
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            globals: HashMap::new(),
+            output: Box::new(io::stdout()),
+        }
+    }
+
+    pub fn with_output(mut self, output: Box<dyn Write>) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub fn run(&mut self, chunk: &Chunk) -> Result<(), RuntimeError> {
+        for op in &chunk.code {
+            match *op {
+                OpCode::Constant(constant) => self.stack.push(chunk.constants[constant].clone()),
+                OpCode::Nil => self.stack.push(Object::L(Literal::Nil(()))),
+                OpCode::True => self.stack.push(Object::L(Literal::Bool(true))),
+                OpCode::False => self.stack.push(Object::L(Literal::Bool(false))),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::DefineGlobal(name) => {
+                    let value = self.pop();
+                    self.globals.insert(name_of(chunk, name), value);
+                }
+                OpCode::GetGlobal(name) => {
+                    let name = name_of(chunk, name);
+                    match self.globals.get(&name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(undefined(&name)),
+                    }
+                }
+                OpCode::SetGlobal(name) => {
+                    let name = name_of(chunk, name);
+                    let value = self.stack.last().unwrap().clone();
+                    match self.globals.get_mut(&name) {
+                        Some(slot) => *slot = value,
+                        None => return Err(undefined(&name)),
+                    }
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let equal = match (left, right) {
+                        (Object::L(left), Object::L(right)) => left == right,
+                        _ => false,
+                    };
+                    self.stack.push(Object::L(Literal::Bool(equal)));
+                }
+                OpCode::Add => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let value = match (left, right) {
+                        (Object::L(Literal::Float(left)), Object::L(Literal::Float(right))) => {
+                            Literal::Float(left + right)
+                        }
+                        (Object::L(Literal::String(left)), Object::L(Literal::String(right))) => {
+                            Literal::concat(&left, &right)
+                        }
+                        _ => {
+                            return Err(RuntimeError::new(
+                                "Operands must be two numbers or two strings.",
+                            ))
+                        }
+                    };
+                    self.stack.push(Object::L(value));
+                }
+                OpCode::Greater
+                | OpCode::GreaterEqual
+                | OpCode::Less
+                | OpCode::LessEqual
+                | OpCode::Subtract
+                | OpCode::Multiply
+                | OpCode::Divide => {
+                    let (left, right) = match (self.pop(), self.pop()) {
+                        (Object::L(Literal::Float(right)), Object::L(Literal::Float(left))) => {
+                            (left, right)
+                        }
+                        _ => return Err(RuntimeError::new("Operands must be numbers.")),
+                    };
+                    let value = match op {
+                        OpCode::Greater => Literal::Bool(left > right),
+                        OpCode::GreaterEqual => Literal::Bool(left >= right),
+                        OpCode::Less => Literal::Bool(left < right),
+                        OpCode::LessEqual => Literal::Bool(left <= right),
+                        OpCode::Subtract => Literal::Float(left - right),
+                        OpCode::Multiply => Literal::Float(left * right),
+                        _ if right == 0.0 => return Err(RuntimeError::new("Division by zero.")),
+                        _ => Literal::Float(left / right),
+                    };
+                    self.stack.push(Object::L(value));
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack
+                        .push(Object::L(Literal::Bool(!is_truthy(&value))));
+                }
+                OpCode::Negate => match self.pop() {
+                    Object::L(Literal::Float(f)) => self.stack.push(Object::L(Literal::Float(-f))),
+                    _ => return Err(RuntimeError::new("Operand must be a number.")),
+                },
+                OpCode::Print(count) => {
+                    let values: Vec<String> = self
+                        .stack
+                        .split_off(self.stack.len() - count)
+                        .iter()
+                        .map(stringify)
+                        .collect();
+                    writeln!(self.output, "{}", values.join(" "))
+                        .map_err(|e| RuntimeError::new(&e.to_string()))?;
+                }
+                OpCode::Return => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Object {
+        self.stack.pop().unwrap()
+    }
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn name_of(chunk: &Chunk, constant: usize) -> String {
+    match &chunk.constants[constant] {
+        Object::L(Literal::String(name)) => name.to_string(),
+        c => panic!("{:?}", c),
+    }
+}
+
+fn undefined(name: &str) -> RuntimeError {
+    RuntimeError::new(&format!("Undefined variable '{}'.", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::error::RuntimeError;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::lex;
+    use crate::parser::ast::Stmt;
+    use crate::parser::parser::Parser;
+    use crate::sync::{Rc, RefCell};
+    use crate::vm::chunk::OpCode;
+    use crate::vm::compiler::Compiler;
+    use crate::vm::VM;
+    use std::io;
+    use std::io::Write;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let input: Vec<char> = source.chars().collect();
+        let tokens = lex(&input).unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    fn run_vm(source: &str) -> Result<String, RuntimeError> {
+        let chunk = Compiler::compile(&parse(source)).unwrap();
+        let output = Buffer::default();
+        VM::new()
+            .with_output(Box::new(output.clone()))
+            .run(&chunk)?;
+        Ok(output.contents())
+    }
+
+    fn run_tree_walker(source: &str) -> String {
+        let output = Buffer::default();
+        Interpreter::new()
+            .with_output(Box::new(output.clone()))
+            .interpret(&parse(source))
+            .unwrap();
+        output.contents()
+    }
+
+    #[test]
+    fn compile() {
+        let chunk = Compiler::compile(&parse("print 1 + 2 * 3;")).unwrap();
+        assert_eq!(
+            chunk.code,
+            vec![
+                OpCode::Constant(0),
+                OpCode::Constant(1),
+                OpCode::Constant(2),
+                OpCode::Multiply,
+                OpCode::Add,
+                OpCode::Print(1),
+                OpCode::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_tree_walker() {
+        for source in &[
+            "print 1 + 2 * 3;",
+            "var a = 1; var b = a + 2; a = b * 10; print a; print -a / 4;",
+            "print (1 + 2) * 3 >= 9; print 1 != 1; print !nil;",
+            r#"var s = "lox"; print s + "!";"#,
+            "var a = 1, b = a + 1; print b;",
+            r#"print 1, "two", 1 < 2;"#,
+            "var a = 0.1; print a++; print a; a--; print a;",
+        ] {
+            assert_eq!(run_vm(source).unwrap(), run_tree_walker(source));
+        }
+
+        // Every ordering comparison with NaN is false, so `>=` can't be compiled as `!(<)`.
+        let big = format!("1{}", "0".repeat(200));
+        let source = format!(
+            "var i = {0} * {0}; var n = i - i; print n >= 0, n <= 0, n > 0, n < 0, n != n;",
+            big
+        );
+        assert_eq!(run_vm(&source).unwrap(), "false false false false true\n");
+        assert_eq!(run_vm(&source).unwrap(), run_tree_walker(&source));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            run_vm("print a;"),
+            Err(RuntimeError::new("Undefined variable 'a'."))
+        );
+        assert_eq!(
+            run_vm("print 1 / 0;"),
+            Err(RuntimeError::new("Division by zero."))
+        );
+        assert_eq!(
+            run_vm(r#"print 1 + "a";"#),
+            Err(RuntimeError::new(
+                "Operands must be two numbers or two strings."
+            ))
+        );
+        assert!(Compiler::compile(&parse("fun f() {}")).is_err());
+    }
+}